@@ -0,0 +1,353 @@
+use std::{
+    fs, io,
+
+    io::Write,
+
+    path::{Path, PathBuf}
+};
+
+use crate::{
+    data::Labels,
+    export,
+    lock::{self, Lock},
+    paths::{self, LOCAL_BOARD_DIR, LOCAL_BOARD_FILE},
+    storage::{self, crypto::{self, Cipher}, journal, Format, Storage}
+};
+
+// Wrong passphrases before giving up
+const PASSPHRASE_ATTEMPTS: usize = 3;
+
+pub const USAGE: &str = "\
+Usage: kanban [--file <path>] [--global] [--restore-backup] [--git-commit[=<seconds>]]
+       kanban init [--markdown]
+       kanban encrypt|decrypt|history [--file <path>] [--global]
+       kanban export [--project <name>] [--output <path>] [--file <path>] [--global]
+
+Without --file the board is .kanban.json or a .kanban directory in the working
+directory or the closest parent that has one, else the global board.
+
+Commands:
+    init                 Create a board for the repository in the working directory
+    encrypt              Encrypt the board and its backups with a passphrase, or
+                         change the passphrase of an encrypted board
+    decrypt              Store the board and its backups in plain text again
+    history              List the changes made since the board file was last compacted
+    export               Print the board as Markdown, a heading per project and a
+                         checklist per column with the last column checked
+
+Options:
+    -f, --file <path>    Board file to open, overrides KANBAN_FILE
+    -g, --global         Open the global board even inside a repository with a board
+        --restore-backup List the backups of the board and restore one
+        --markdown       With init, create a .kanban directory of Markdown files
+    -p, --project <name> With export, only the project with this name
+    -o, --output <path>  With export, write to this file instead of the terminal
+        --git-commit[=<seconds>]
+                         Commit the saved changes of a board inside a git work tree,
+                         once no change was made for <seconds>, every save without
+    -h, --help           Show this message
+
+Environment:
+    KANBAN_FILE          Board file to open when --file is not given
+    KANBAN_GIT_COMMIT    Seconds for --git-commit when it is not given";
+
+// * Command line arguments
+pub enum Command {
+    Init,
+    Encrypt,
+    Decrypt,
+    History,
+    Export
+}
+
+pub struct Args {
+    pub command: Option<Command>,
+    pub file: Option<PathBuf>,
+    pub global: bool,
+    pub restore_backup: bool,
+    pub markdown: bool,
+    pub git_commit: Option<u64>,
+    pub project: Option<String>,
+    pub output: Option<PathBuf>,
+    pub help: bool
+}
+
+impl Args {
+
+    pub fn parse() -> Result<Args, String> {
+
+        Args::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+        let mut parsed = Args {
+            command: None,
+            file: None,
+            global: false,
+            restore_backup: false,
+            markdown: false,
+            git_commit: None,
+            project: None,
+            output: None,
+            help: false
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-f" | "--file" => {
+                    let file = args.next().ok_or(format!("{} needs a path", arg))?;
+                    parsed.file = Some(PathBuf::from(file));
+                },
+                "-g" | "--global" => parsed.global = true,
+                "--restore-backup" => parsed.restore_backup = true,
+                "--markdown" => parsed.markdown = true,
+                "--git-commit" => parsed.git_commit = Some(0),
+                "-p" | "--project" => parsed.project = Some(args.next().ok_or(format!("{} needs a project name", arg))?),
+                "-o" | "--output" => {
+                    let output = args.next().ok_or(format!("{} needs a path", arg))?;
+                    parsed.output = Some(PathBuf::from(output));
+                },
+                "init" if parsed.command.is_none() => parsed.command = Some(Command::Init),
+                "encrypt" if parsed.command.is_none() => parsed.command = Some(Command::Encrypt),
+                "decrypt" if parsed.command.is_none() => parsed.command = Some(Command::Decrypt),
+                "history" if parsed.command.is_none() => parsed.command = Some(Command::History),
+                "export" if parsed.command.is_none() => parsed.command = Some(Command::Export),
+                "-h" | "--help" => parsed.help = true,
+                _ => {
+                    if let Some(file) = arg.strip_prefix("--file=") {
+                        parsed.file = Some(PathBuf::from(file));
+                    } else if let Some(seconds) = arg.strip_prefix("--git-commit=") {
+                        parsed.git_commit = Some(seconds.parse().map_err(|_| format!("--git-commit needs a number of seconds: {}", arg))?);
+                    } else {
+                        return Err(format!("unknown argument: {}", arg));
+                    }
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+// Creates an empty board in the working directory for `kanban init`
+pub fn init_board(markdown: bool) -> io::Result<()> {
+    let dir = std::env::current_dir()?;
+
+    if let Some(existing) = paths::find_local_board(&dir).filter(|board| board.parent() == Some(dir.as_path())) {
+        println!("{} already exists", existing.display());
+        return Ok(());
+    }
+
+    let board = create_local_board(&dir, markdown)?;
+
+    let board_name = board.file_name().unwrap_or_default().to_string_lossy();
+
+    println!("Created {}", board.display());
+    println!("kanban opens it from {} and every directory below it", dir.display());
+    println!("Lock, state and backup files are kept next to it, add {0}.lock, {0}.state.json and {0}.backups/ to .gitignore", board_name);
+
+    Ok(())
+}
+
+// .kanban.json, or the .kanban directory with --markdown, holding an empty board
+fn create_local_board(dir: &Path, markdown: bool) -> io::Result<PathBuf> {
+    let board = dir.join(if markdown {LOCAL_BOARD_DIR} else {LOCAL_BOARD_FILE});
+
+    // a path that does not exist yet is only a Markdown board with a trailing separator
+    if markdown {
+        fs::create_dir_all(&board)?;
+    }
+
+    storage::open(&board, None).save(&[], &[], &Labels::new())?;
+
+    Ok(board)
+}
+
+// * Encryption
+// Asks for the passphrase when the board file is encrypted, None for a plain board
+pub fn unlock_board(path: &Path) -> io::Result<Option<Cipher>> {
+
+    if storage::format_of(path) != Format::Json {
+        return Ok(None);
+    }
+
+    let content = match fs::read(path) {
+        Ok(content) if crypto::is_encrypted(&content) => content,
+        Ok(_) => return Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e)
+    };
+
+    for _ in 0..PASSPHRASE_ATTEMPTS {
+        let passphrase = rpassword::prompt_password(format!("Passphrase for {}: ", path.display()))?;
+
+        match Cipher::for_file(&passphrase, &content) {
+            Ok(cipher) => return Ok(Some(cipher)),
+            Err(e) => eprintln!("{}", e)
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} could not be decrypted", path.display())))
+}
+
+fn new_passphrase() -> io::Result<Cipher> {
+    let passphrase = rpassword::prompt_password("New passphrase: ")?;
+
+    if passphrase.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the passphrase is empty"));
+    }
+
+    if rpassword::prompt_password("Repeat the passphrase: ")? != passphrase {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the passphrases do not match"));
+    }
+
+    Cipher::new(&passphrase).map_err(io::Error::other)
+}
+
+// `kanban encrypt` and `kanban decrypt`, the board and all of its backups are
+// rewritten in place so no plain copy is left behind in the backup directory
+pub fn change_encryption(path: &Path, encrypt: bool) -> io::Result<()> {
+
+    if storage::format_of(path) != Format::Json {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "only JSON board files can be encrypted"));
+    }
+
+    if !path.is_file() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())));
+    }
+
+    // a running instance would write the board back the way it was
+    let _lock = match lock::acquire(path)? {
+        Lock::Acquired(lock) => lock,
+        Lock::HeldBy(_) => return Err(io::Error::new(io::ErrorKind::WouldBlock,
+                                format!("{} is open in another kanban, close it first", path.display())))
+    };
+
+    let old = unlock_board(path)?;
+
+    if !encrypt && old.is_none() {
+        println!("{} is not encrypted", path.display());
+        return Ok(());
+    }
+
+    let new = if encrypt {Some(new_passphrase()?)} else {None};
+    let skipped = storage::change_encryption(path, old.as_ref(), new.as_ref())?;
+
+    match (encrypt, old.is_some()) {
+        (true, true) => println!("Changed the passphrase of {}", path.display()),
+        (true, false) => println!("Encrypted {}, it can not be opened without the passphrase", path.display()),
+        (false, _) => println!("Decrypted {}", path.display())
+    }
+
+    for file in skipped {
+        println!("{} could not be decrypted with the passphrase and was left as it is", file.display());
+    }
+
+    Ok(())
+}
+
+// `kanban history`, the journal of a JSON board oldest first
+pub fn print_history(path: &Path, cipher: Option<&Cipher>) -> io::Result<()> {
+
+    if storage::format_of(path) != Format::Json {
+        println!("Only JSON board files keep a journal of their changes");
+        return Ok(());
+    }
+
+    let journal = journal::read(path, cipher).map_err(|e| io::Error::other(e.message()))?;
+
+    if journal.entries.is_empty() {
+        println!("No changes since {} was last written in full", path.display());
+    }
+
+    for entry in journal.entries {
+        println!("{}  {:<6}  {}", entry.at.format("%d-%m-%Y %H:%M:%S"), entry.op.name(), entry.describe());
+    }
+
+    Ok(())
+}
+
+// `kanban export`, the whole board or one project by name
+pub fn export(storage: &mut dyn Storage, project: Option<&str>, output: Option<&Path>) -> io::Result<()> {
+    let board = storage.load().map_err(|e| io::Error::other(e.message()))?;
+
+    let markdown = match project {
+        Some(name) => match board.projects.iter().find(|project| project.name.eq_ignore_ascii_case(name.trim())) {
+            Some(project) => export::project_to_markdown(project),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("there is no project named {}", name)))
+        },
+        None => export::board_to_markdown(&board.projects)
+    };
+
+    match output {
+        Some(path) => fs::write(path, markdown),
+        None => {
+            print!("{}", markdown);
+            io::stdout().flush()
+        }
+    }
+}
+
+// Lists the backups of a board on the terminal and restores the chosen one
+pub fn restore_backup_prompt(path: &Path) -> io::Result<()> {
+    let backups = storage::list_backups(path)?;
+
+    if backups.is_empty() {
+        println!("No backups of {} found in {}", path.display(), storage::backup_dir(path).display());
+        return Ok(());
+    }
+
+    println!("Backups of {}, newest first:\n", path.display());
+
+    for (i, backup) in backups.iter().enumerate() {
+        println!("{:>4}  {}  {:>8} bytes  {}", i + 1,
+            backup.modified.format("%d-%m-%Y %H:%M:%S"), backup.size,
+            backup.path.file_name().unwrap_or_default().to_string_lossy());
+    }
+
+    print!("\nRestore which backup? [1-{}, empty to cancel] ", backups.len());
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    let backup = match answer.trim().parse::<usize>() {
+        Ok(i) if i >= 1 && i <= backups.len() => &backups[i - 1],
+        _ => {
+            println!("Nothing restored");
+            return Ok(());
+        }
+    };
+
+    storage::restore_backup(path, backup)?;
+    println!("Restored {}, the replaced board was backed up as well", backup.path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kanban-cli-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn init_creates_a_board_that_is_found() {
+
+        for markdown in [false, true] {
+            let dir = temp_dir(if markdown {"markdown"} else {"json"});
+            let board = create_local_board(&dir, markdown).unwrap();
+
+            assert_eq!(board.is_dir(), markdown);
+            assert_eq!(paths::find_local_board(&dir.join("src").join("nested")), Some(board));
+
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
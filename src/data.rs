@@ -1,23 +1,58 @@
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::PathBuf;
 use std::collections::BTreeMap;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use tui::widgets::ListState;
+use tui::style::Color;
+
+use chrono::prelude::*;
+
+use crate::editor::TextEditor;
+use crate::storage::{self, crypto::Cipher, Stamp, Storage};
+use crate::lock::BoardLock;
+use crate::git::AutoCommit;
 
 use serde_with::{serde_as, DurationSeconds};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 // * State of the App
 pub struct App {
     pub tab: BTreeMap<i32, String>,
+    #[allow(dead_code)]
     pub test_int: i32,
     pub daily_task: DailyTask,
     pub kanban: Kanban,
     pub focus: Focus,
     pub chunk_size: Vec<i32>,
+    #[allow(dead_code)]
     pub messages: Message,
     pub popup: Popup,
     pub input: String,
-    pub can_input: bool
+    pub can_input: bool,
+    pub editor: Option<TextEditor>,
+    pub board_path: PathBuf,
+    // Which board is open, shown in the header
+    pub board_label: String,
+    pub storage: Box<dyn Storage>,
+    // Set when the board file is encrypted
+    pub cipher: Option<Cipher>,
+    pub status: Option<String>,
+    // Nothing is written to the board file while set
+    pub read_only: bool,
+    pub recovery: Option<Recovery>,
+    // The board file as it was last read or written by us
+    pub stamp: Stamp,
+    // Changes that are not on disk yet
+    pub unsaved: bool,
+    // The board was changed on disk while there were unsaved changes
+    pub conflict: bool,
+    pub lock: Option<BoardLock>,
+    // Another instance holds the lock, the board is read-only here
+    pub locked_out: bool,
+    // Set with --git-commit for a board inside a git work tree
+    pub auto_commit: Option<AutoCommit>
 }
 
 impl App {
@@ -32,36 +67,75 @@ impl App {
             daily_task: DailyTask::default(),
             kanban: Kanban::default(),
             focus: Focus::default(),
-            chunk_size: vec![0, 1, 0, 2], // actual size -1 for indexing
+            chunk_size: vec![0, 1, 0, 0], // actual size -1 for indexing, kanban uses its column count
             messages: Message::default(),
             popup: Popup::Disabled,
             input: String::from(""),
-            can_input: false
+            can_input: false,
+            editor: None,
+            board_path: PathBuf::new(),
+            board_label: String::new(),
+            storage: storage::open(&PathBuf::new(), None),
+            cipher: None,
+            status: None,
+            read_only: false,
+            recovery: None,
+            stamp: Stamp::default(),
+            unsaved: false,
+            conflict: false,
+            lock: None,
+            locked_out: false,
+            auto_commit: None
         }
     }
 }
 
 pub enum Popup {
+    // ! Daily task popups are not wired to the UI yet
+    #[allow(dead_code)]
     AddTask,
+    #[allow(dead_code)]
     EditTask,
+    #[allow(dead_code)]
     DeleteTask,
     AddProject,
     EditProject,
     DeleteProject,
-    AddTodo,
-    EditTodo,
-    DeleteTodo,
-    AddInProgress,
-    EditInProgress,
-    DeleteInProgress,
-    AddDone,
-    EditDone,
-    DeleteDone,
+    AddCard,
+    EditCard,
+    DeleteCard,
+    AddColumn,
+    RenameColumn,
+    DeleteColumn,
+    SetWipLimit,
+    ConfirmMove,
+    AddLabel,
+    RemoveLabel,
+    Checklist,
+    AddChecklistItem,
+    EditChecklistItem,
+    CardDetail,
+    EditDescription,
+    GoToCard,
+    ExportProject,
+    ExportBoard,
+    Recovery,
+    Conflict,
+    Locked,
     Disabled
 }
 
+// Why the board file could not be loaded, shown on the recovery screen
+pub struct Recovery {
+    pub error: String,
+    pub location: Option<(usize, usize)>,
+    pub note: Option<String>
+}
+
 pub struct Message {
+    #[allow(dead_code)]
     pub quit: String,
+    #[allow(dead_code)]
     pub tab_change: String
 }
 
@@ -96,6 +170,7 @@ pub struct DailyTask {
     pub tasks: Vec<Task>,
     pub selected_task_index: usize,
     pub selected_step_index: usize,
+    #[allow(dead_code)]
     pub daily_task_list_state: ListState,
     pub daily_task_step_list_state: ListState
 }
@@ -113,6 +188,14 @@ impl DailyTask {
         }
     }
 
+    // Tasks read from the board file, the selection stays in range
+    pub fn replace_tasks(&mut self, tasks: Vec<Task>) {
+
+        self.tasks = tasks;
+        self.selected_task_index = self.selected_task_index.min(self.tasks.len().saturating_sub(1));
+    }
+
+    #[allow(dead_code)]
     pub fn add_task(&mut self, task: Task) {
 
         self.tasks.push(task);
@@ -123,6 +206,7 @@ impl DailyTask {
 #[derive(Deserialize, Serialize)]
 pub struct Task {
     pub task_name: String,
+    #[serde(default)]
     pub steps: Vec<TaskStep>,
     #[serde_as(as = "DurationSeconds<u64>")]
     pub task_duration: Duration
@@ -130,6 +214,7 @@ pub struct Task {
 
 impl Task {
 
+    #[allow(dead_code)]
     pub fn new(name: &str) -> Task {
 
         Task{
@@ -139,18 +224,22 @@ impl Task {
         }
     }
 
+    #[allow(dead_code)]
     pub fn add_step(&mut self, step: TaskStep) {
 
         self.steps.push(step);
     }
 }
 
+// Also used as a checklist item of kanban cards
 #[serde_as]
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct TaskStep {
     pub step_name: String,
     #[serde_as(as = "DurationSeconds<u64>")]
-    pub step_duration: Duration
+    pub step_duration: Duration,
+    #[serde(default)]
+    pub done: bool
 }
 
 impl TaskStep {
@@ -159,7 +248,8 @@ impl TaskStep {
 
         TaskStep {
             step_name: String::from(name),
-            step_duration
+            step_duration,
+            done: false
         }
     }
 
@@ -173,13 +263,15 @@ impl TaskStep {
 
 pub struct Kanban {
     pub projects : Vec<KanbanProject>,
+    pub labels : Labels,
     pub project_index : usize,
-    pub todo_state : ListState,
-    pub todo_index : usize,
-    pub in_progress_state : ListState,
-    pub in_progress_index : usize,
-    pub done_state : ListState,
-    pub done_index : usize
+    pub column_index : usize,
+    pub card_index : usize,
+    pub column_states : Vec<ListState>,
+    pub pending_move : Option<usize>,
+    pub checklist_index : usize,
+    pub checklist_state : ListState,
+    pub detail_scroll : u16
 }
 
 impl Kanban {
@@ -188,13 +280,15 @@ impl Kanban {
         
         Kanban {
             projects: Vec::new(),
+            labels: Labels::new(),
             project_index: 0,
-            todo_state: ListState::default(),
-            todo_index: 0,
-            in_progress_state: ListState::default(),
-            in_progress_index: 1000,
-            done_state: ListState::default(),
-            done_index: 1000
+            column_index: 0,
+            card_index: 0,
+            column_states: Vec::new(),
+            pending_move: None,
+            checklist_index: 0,
+            checklist_state: ListState::default(),
+            detail_scroll: 0
         }
     }
 
@@ -203,38 +297,797 @@ impl Kanban {
         self.projects.push(project);
     }
 
-    pub fn add_todo(&mut self, index: usize, todo_name: &str) {
+    pub fn label_color(&self, label: &str) -> Color {
+
+        self.labels.get(label).copied().unwrap_or(Color::Gray)
+    }
+
+    // Adds a label to the palette, new labels without a colour get the next one of LABEL_COLORS
+    pub fn define_label(&mut self, label: &str, color: Option<Color>) {
+
+        match color {
+            Some(color) => {
+                self.labels.insert(String::from(label), color);
+            },
+            None => {
+                let next = LABEL_COLORS[self.labels.len() % LABEL_COLORS.len()];
+                self.labels.entry(String::from(label)).or_insert(next);
+            }
+        }
+    }
+
+    pub fn project(&self) -> &KanbanProject {
+
+        &self.projects[self.project_index]
+    }
+
+    pub fn project_mut(&mut self) -> &mut KanbanProject {
+
+        &mut self.projects[self.project_index]
+    }
+
+    pub fn column(&self) -> &KanbanColumn {
+
+        &self.project().columns[self.column_index]
+    }
+
+    pub fn column_mut(&mut self) -> &mut KanbanColumn {
+        let column_index = self.column_index;
+
+        &mut self.project_mut().columns[column_index]
+    }
+
+    // None on a board without projects as well, e.g. after reloading an empty one
+    pub fn selected_card(&self) -> Option<&Card> {
+
+        self.projects.get(self.project_index)?.columns.get(self.column_index)?.cards.get(self.card_index)
+    }
+
+    pub fn selected_card_mut(&mut self) -> Option<&mut Card> {
+
+        self.projects.get_mut(self.project_index)?.columns.get_mut(self.column_index)?.cards.get_mut(self.card_index)
+    }
+
+    // Swaps in a board read again from disk, the selection stays on the same project and card
+    pub fn replace_projects(&mut self, projects: Vec<KanbanProject>) {
+        let project_id = self.projects.get(self.project_index).map(|project| project.id.clone());
+        let card_id = if self.projects.is_empty() {None} else {self.selected_card().map(|card| card.id.clone())};
+
+        self.projects = projects;
+
+        if self.projects.is_empty() {
+            self.project_index = 0;
+            self.reset_selection();
+            return;
+        }
+
+        self.project_index = project_id
+                                .and_then(|id| self.projects.iter().position(|project| project.id == id))
+                                .unwrap_or_else(|| self.project_index.min(self.projects.len() - 1));
+
+        if !card_id.is_some_and(|id| self.select_card(&id)) {
+            self.clamp_selection();
+        }
+    }
+
+    // Back to the first card of the first column, e.g. after switching projects
+    pub fn reset_selection(&mut self) {
+
+        self.column_index = 0;
+        self.card_index = 0;
+    }
+
+    // Keeps the indexes valid after cards or columns were removed
+    pub fn clamp_selection(&mut self) {
+        let column_count = self.project().columns.len();
 
-        self.projects[index].todo.push(String::from(todo_name));
+        if self.column_index >= column_count {
+            self.column_index = column_count.saturating_sub(1);
+        }
+
+        let card_count = self.column().cards.len();
+
+        if self.card_index >= card_count {
+            self.card_index = card_count.saturating_sub(1);
+        }
     }
 
-    pub fn add_in_progress(&mut self, index: usize, in_progress_name: &str) {
+    pub fn add_card(&mut self, mut card: Card) {
 
-        self.projects[index].in_progress.push(String::from(in_progress_name));
+        card.number = self.project_mut().take_card_number();
+        self.column_mut().cards.push(card);
+        self.column_mut().sort();
     }
 
-    pub fn add_done(&mut self, index: usize, done_name: &str) {
+    // Points the selection at a card again, e.g. after sorting
+    pub fn select_card(&mut self, id: &str) -> bool {
 
-        self.projects[index].done.push(String::from(done_name));
+        match self.project().find_card(id) {
+            Some((column_index, card_index)) => {
+                self.column_index = column_index;
+                self.card_index = card_index;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn select_card_by_number(&mut self, number: u32) -> bool {
+
+        match self.project().find_card_by_number(number) {
+            Some((column_index, card_index)) => {
+                self.column_index = column_index;
+                self.card_index = card_index;
+                true
+            },
+            None => false
+        }
+    }
+
+    // Raises or lowers the priority of the selected card, the selection follows it when the column is sorted
+    pub fn change_priority(&mut self, raise: bool) -> bool {
+        let id = match self.selected_card_mut() {
+            Some(card) => {
+                let priority = if raise {card.priority.higher()} else {card.priority.lower()};
+
+                if priority == card.priority {
+                    return false;
+                }
+
+                card.priority = priority;
+                card.updated_at = Local::now();
+                card.id.clone()
+            },
+            None => return false
+        };
+
+        self.column_mut().sort();
+        self.select_card(&id);
+
+        true
+    }
+
+    pub fn delete_card(&mut self) {
+        let card_index = self.card_index;
+
+        if card_index < self.column().cards.len() {
+            self.column_mut().cards.remove(card_index);
+            self.clamp_selection();
+        }
+    }
+
+    // Moves the selected card to the end of another column, the focus stays where it was
+    pub fn move_card(&mut self, to_column: usize) -> bool {
+        let card_index = self.card_index;
+
+        if to_column >= self.project().columns.len() || card_index >= self.column().cards.len() {
+            return false;
+        }
+
+        let from = self.column().name.clone();
+        let to = self.project().columns[to_column].name.clone();
+
+        let mut card = self.column_mut().cards.remove(card_index);
+        card.transitions.push(Transition {
+            from,
+            to,
+            at: Local::now()
+        });
+
+        self.project_mut().columns[to_column].cards.push(card);
+        self.project_mut().columns[to_column].sort();
+        self.clamp_selection();
+
+        true
+    }
+
+    // New columns are inserted right of the focused one and take the focus
+    pub fn add_column(&mut self, name: &str) {
+        let position = if self.project().columns.is_empty() {0} else {self.column_index + 1};
+
+        self.project_mut().columns.insert(position, KanbanColumn::new(name));
+        self.column_index = position;
+        self.card_index = 0;
+    }
+
+    pub fn rename_column(&mut self, name: &str) {
+
+        self.column_mut().name = String::from(name);
+    }
+
+    // Swaps the focused column with its neighbour, the focus follows the column
+    pub fn move_column(&mut self, to_column: usize) -> bool {
+
+        if to_column >= self.project().columns.len() || to_column == self.column_index {
+            return false;
+        }
+
+        let column_index = self.column_index;
+        self.project_mut().columns.swap(column_index, to_column);
+        self.column_index = to_column;
+
+        true
+    }
+
+    // Open cards past their due date over all projects
+    pub fn overdue_count(&self, today: NaiveDate) -> usize {
+
+        self.projects.iter()
+            .map(|project| project.open_cards().filter(|card| card.is_overdue(today)).count())
+            .sum()
+    }
+
+    // The last column of a project can not be deleted
+    pub fn delete_column(&mut self) -> bool {
+
+        if self.project().columns.len() <= 1 {
+            return false;
+        }
+
+        let column_index = self.column_index;
+        self.project_mut().columns.remove(column_index);
+        self.card_index = 0;
+        self.clamp_selection();
+
+        true
     }
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(from = "StoredProject")]
 pub struct KanbanProject {
+    pub id: String,
     pub name: String,
-    pub todo : Vec<String>,
-    pub in_progress :  Vec<String>,
-    pub done : Vec<String>
+    pub columns: Vec<KanbanColumn>,
+    // Numbers are never reused, even after a card was deleted
+    pub next_card_number: u32
 }
 
 impl KanbanProject {
 
     pub fn new(name: String) -> KanbanProject {
         KanbanProject {
+            id: new_id(),
             name,
-            todo: Vec::new(),
-            in_progress: Vec::new(),
-            done: Vec::new()
+            columns: vec![
+                KanbanColumn::new("ToDo"),
+                KanbanColumn::new("In Progress"),
+                KanbanColumn::new("Done")
+            ],
+            next_card_number: 1
+        }
+    }
+
+    pub fn take_card_number(&mut self) -> u32 {
+        let number = self.next_card_number;
+
+        self.next_card_number += 1;
+        number
+    }
+
+    // (column index, card index) of a card
+    pub fn find_card(&self, id: &str) -> Option<(usize, usize)> {
+
+        self.columns.iter().enumerate().find_map(|(column_index, column)| {
+            column.cards.iter().position(|card| card.id == id).map(|card_index| (column_index, card_index))
+        })
+    }
+
+    pub fn find_card_by_number(&self, number: u32) -> Option<(usize, usize)> {
+
+        self.columns.iter().enumerate().find_map(|(column_index, column)| {
+            column.cards.iter().position(|card| card.number == number).map(|card_index| (column_index, card_index))
+        })
+    }
+
+    // Gives numbers to cards stored before they had one
+    fn number_cards(&mut self) {
+        let highest = self.columns.iter()
+            .flat_map(|column| column.cards.iter())
+            .map(|card| card.number)
+            .max()
+            .unwrap_or(0);
+
+        self.next_card_number = self.next_card_number.max(highest + 1);
+
+        for column_index in 0..self.columns.len() {
+            for card_index in 0..self.columns[column_index].cards.len() {
+                if self.columns[column_index].cards[card_index].number == 0 {
+                    let number = self.take_card_number();
+                    self.columns[column_index].cards[card_index].number = number;
+                }
+            }
+        }
+    }
+
+    // The last column holds the finished cards
+    pub fn is_done_column(&self, column_index: usize) -> bool {
+
+        column_index + 1 == self.columns.len()
+    }
+
+    // Cards of every column except the done column
+    pub fn open_cards(&self) -> impl Iterator<Item = &Card> {
+        let open_columns = self.columns.len().saturating_sub(1);
+
+        self.columns[..open_columns].iter().flat_map(|column| column.cards.iter())
+    }
+}
+
+// Label palette of the board, shared by all projects. Cards refer to labels by name
+pub type Labels = BTreeMap<String, Color>;
+
+pub const LABEL_COLORS: [Color; 6] = [
+    Color::LightBlue,
+    Color::LightGreen,
+    Color::LightMagenta,
+    Color::LightYellow,
+    Color::LightCyan,
+    Color::LightRed
+];
+
+pub fn color_from_name(name: &str) -> Option<Color> {
+
+    match name.trim().to_lowercase().replace(['-', '_', ' '], "").as_str() {
+        "black" => Some(Color::Black),
+        "red" => Some(Color::Red),
+        "green" => Some(Color::Green),
+        "yellow" => Some(Color::Yellow),
+        "blue" => Some(Color::Blue),
+        "magenta" => Some(Color::Magenta),
+        "cyan" => Some(Color::Cyan),
+        "gray" | "grey" => Some(Color::Gray),
+        "darkgray" | "darkgrey" => Some(Color::DarkGray),
+        "lightred" => Some(Color::LightRed),
+        "lightgreen" => Some(Color::LightGreen),
+        "lightyellow" => Some(Color::LightYellow),
+        "lightblue" => Some(Color::LightBlue),
+        "lightmagenta" => Some(Color::LightMagenta),
+        "lightcyan" => Some(Color::LightCyan),
+        "white" => Some(Color::White),
+        _ => None
+    }
+}
+
+// Projects written before columns were configurable had three fixed card lists
+#[derive(Deserialize)]
+struct StoredProject {
+    #[serde(default = "new_id")]
+    id: String,
+    name: String,
+    columns: Option<Vec<KanbanColumn>>,
+    #[serde(default)]
+    next_card_number: u32,
+    #[serde(default, deserialize_with = "deserialize_cards")]
+    todo: Vec<Card>,
+    #[serde(default, deserialize_with = "deserialize_cards")]
+    in_progress: Vec<Card>,
+    #[serde(default, deserialize_with = "deserialize_cards")]
+    done: Vec<Card>
+}
+
+impl From<StoredProject> for KanbanProject {
+
+    fn from(stored: StoredProject) -> KanbanProject {
+
+        let mut project = match stored.columns {
+            Some(columns) if !columns.is_empty() => KanbanProject {
+                id: stored.id,
+                name: stored.name,
+                columns,
+                next_card_number: stored.next_card_number
+            },
+            _ => KanbanProject {
+                id: stored.id,
+                name: stored.name,
+                columns: vec![
+                    KanbanColumn::with_cards("ToDo", stored.todo),
+                    KanbanColumn::with_cards("In Progress", stored.in_progress),
+                    KanbanColumn::with_cards("Done", stored.done)
+                ],
+                next_card_number: stored.next_card_number
+            }
+        };
+
+        project.number_cards();
+        project
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KanbanColumn {
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_cards")]
+    pub cards: Vec<Card>,
+    #[serde(default)]
+    pub wip_limit: Option<usize>,
+    #[serde(default)]
+    pub sort_by_priority: bool
+}
+
+impl KanbanColumn {
+
+    pub fn new(name: &str) -> KanbanColumn {
+
+        KanbanColumn::with_cards(name, Vec::new())
+    }
+
+    pub fn with_cards(name: &str, cards: Vec<Card>) -> KanbanColumn {
+
+        KanbanColumn {
+            name: String::from(name),
+            cards,
+            wip_limit: None,
+            sort_by_priority: false
         }
     }
+
+    // Highest priority first, keeps the order of cards with the same priority
+    pub fn sort(&mut self) {
+
+        if self.sort_by_priority {
+            self.cards.sort_by_key(|card| Reverse(card.priority));
+        }
+    }
+
+    // Another card would exceed the work in progress limit
+    pub fn is_full(&self) -> bool {
+
+        matches!(self.wip_limit, Some(limit) if self.cards.len() >= limit)
+    }
+
+    pub fn is_over_limit(&self) -> bool {
+
+        matches!(self.wip_limit, Some(limit) if self.cards.len() > limit)
+    }
+}
+
+// * A single kanban card
+// New fields must be `#[serde(default)]` so older files keep loading
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Card {
+    pub id: String,
+    // Short number unique within the project, shown as #42
+    #[serde(default)]
+    pub number: u32,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    #[serde(default)]
+    pub checklist: Vec<TaskStep>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>
+}
+
+impl Card {
+
+    pub fn new(title: &str) -> Card {
+        let now = Local::now();
+
+        Card {
+            id: new_id(),
+            number: 0,
+            title: String::from(title),
+            description: String::new(),
+            labels: Vec::new(),
+            priority: Priority::Normal,
+            due: None,
+            checklist: Vec::new(),
+            transitions: Vec::new(),
+            created_at: now,
+            updated_at: now
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+
+        self.title = String::from(title);
+        self.updated_at = Local::now();
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+
+        if self.description != description {
+            self.description = String::from(description);
+            self.updated_at = Local::now();
+        }
+    }
+
+    pub fn set_due(&mut self, due: Option<NaiveDate>) {
+
+        if self.due != due {
+            self.due = due;
+            self.updated_at = Local::now();
+        }
+    }
+
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+
+        matches!(self.due, Some(due) if due < today)
+    }
+
+    pub fn is_due_today(&self, today: NaiveDate) -> bool {
+
+        self.due == Some(today)
+    }
+
+    // Title and due date the way they are typed in the add/edit popup
+    pub fn edit_text(&self) -> String {
+
+        match self.due {
+            Some(due) => format!("{} @{}", self.title, due.format(DUE_DATE_FORMAT)),
+            None => self.title.clone()
+        }
+    }
+
+    pub fn add_checklist_item(&mut self, name: &str) {
+
+        self.checklist.push(TaskStep::new(name, Duration::from_secs(0)));
+        self.updated_at = Local::now();
+    }
+
+    pub fn toggle_checklist_item(&mut self, index: usize) -> bool {
+
+        match self.checklist.get_mut(index) {
+            Some(item) => {
+                item.done = !item.done;
+                self.updated_at = Local::now();
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn rename_checklist_item(&mut self, index: usize, name: &str) -> bool {
+
+        match self.checklist.get_mut(index) {
+            Some(item) => {
+                item.step_name = String::from(name);
+                self.updated_at = Local::now();
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn remove_checklist_item(&mut self, index: usize) -> bool {
+
+        if index >= self.checklist.len() {
+            return false;
+        }
+
+        self.checklist.remove(index);
+        self.updated_at = Local::now();
+        true
+    }
+
+    // (done, total), None when the card has no checklist
+    pub fn checklist_progress(&self) -> Option<(usize, usize)> {
+
+        if self.checklist.is_empty() {
+            return None;
+        }
+
+        Some((self.checklist.iter().filter(|item| item.done).count(), self.checklist.len()))
+    }
+
+    pub fn unchecked_count(&self) -> usize {
+
+        self.checklist.iter().filter(|item| !item.done).count()
+    }
+
+    // When the card arrived in the column it is in now
+    pub fn entered_column_at(&self) -> DateTime<Local> {
+
+        self.transitions.last().map(|transition| transition.at).unwrap_or(self.created_at)
+    }
+
+    // Creation until the card reached the done column, only for finished cards
+    pub fn lead_time(&self, done: bool) -> Option<chrono::Duration> {
+
+        if !done {
+            return None;
+        }
+
+        Some(self.entered_column_at() - self.created_at)
+    }
+
+    // First move out of the starting column until the card reached the done column
+    pub fn cycle_time(&self, done: bool) -> Option<chrono::Duration> {
+
+        if !done {
+            return None;
+        }
+
+        self.transitions.first().map(|started| self.entered_column_at() - started.at)
+    }
+
+    pub fn add_label(&mut self, label: &str) {
+
+        if !self.labels.iter().any(|l| l == label) {
+            self.labels.push(String::from(label));
+            self.updated_at = Local::now();
+        }
+    }
+
+    pub fn remove_label(&mut self, label: &str) -> bool {
+        let count = self.labels.len();
+        self.labels.retain(|l| l != label);
+
+        if self.labels.len() == count {
+            return false;
+        }
+
+        self.updated_at = Local::now();
+        true
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent
+}
+
+impl Priority {
+
+    pub fn higher(self) -> Priority {
+
+        match self {
+            Priority::Low => Priority::Normal,
+            Priority::Normal => Priority::High,
+            Priority::High | Priority::Urgent => Priority::Urgent
+        }
+    }
+
+    pub fn lower(self) -> Priority {
+
+        match self {
+            Priority::Low | Priority::Normal => Priority::Low,
+            Priority::High => Priority::Normal,
+            Priority::Urgent => Priority::High
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+
+        match self {
+            Priority::Low => "Low",
+            Priority::Normal => "Normal",
+            Priority::High => "High",
+            Priority::Urgent => "Urgent"
+        }
+    }
+
+    // Marker shown in front of the card title, normal priority has none
+    pub fn glyph(self) -> &'static str {
+
+        match self {
+            Priority::Low => "↓ ",
+            Priority::Normal => "",
+            Priority::High => "! ",
+            Priority::Urgent => "‼ "
+        }
+    }
+
+    pub fn color(self) -> Color {
+
+        match self {
+            Priority::Low => Color::DarkGray,
+            Priority::Normal => Color::White,
+            Priority::High => Color::Yellow,
+            Priority::Urgent => Color::Red
+        }
+    }
+}
+
+// A move of a card between two columns, columns are stored by name at the time of the move
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    pub at: DateTime<Local>
+}
+
+// "4 days", "3 hours", "12 minutes"
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let (count, unit) = if duration.num_days() > 0 {
+        (duration.num_days(), "day")
+    } else if duration.num_hours() > 0 {
+        (duration.num_hours(), "hour")
+    } else {
+        (duration.num_minutes().max(0), "minute")
+    };
+
+    format!("{} {}{}", count, unit, if count == 1 {""} else {"s"})
+}
+
+pub const DUE_DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Splits "Fix login @2021-12-24" into the title and the due date,
+// a last word starting with @ that is not a date stays in the title
+pub fn split_due_date(input: &str) -> (String, Option<NaiveDate>) {
+    let input = input.trim();
+
+    if let Some((title, due)) = input.rsplit_once(" @") {
+        if let Ok(due) = NaiveDate::parse_from_str(due.trim(), DUE_DATE_FORMAT) {
+            return (String::from(title.trim_end()), Some(due));
+        }
+    }
+
+    (String::from(input), None)
+}
+
+// Time based id with a per-process counter so ids created in the same instant differ
+// The process id keeps two instances started at the same time apart
+fn new_id() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{:x}-{:x}-{:04x}", nanos, std::process::id(), count & 0xffff)
+}
+
+// Old kanban.json files stored every card as a bare title string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredCard {
+    Card(Card),
+    Title(String)
+}
+
+fn deserialize_cards<'de, D>(deserializer: D) -> Result<Vec<Card>, D::Error>
+where
+    D: Deserializer<'de>
+{
+    let stored: Vec<StoredCard> = Vec::deserialize(deserializer)?;
+
+    Ok(stored.into_iter().map(|card| match card {
+        StoredCard::Card(card) => card,
+        StoredCard::Title(title) => Card::new(&title)
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_strings_become_cards() {
+        let card = serde_json::to_value(Card::new("Fix the header")).unwrap();
+        let column: KanbanColumn = serde_json::from_value(serde_json::json!({
+            "name": "ToDo",
+            "cards": ["Write the docs", card]
+        })).unwrap();
+
+        assert_eq!(column.cards[0].title, "Write the docs");
+        assert_eq!(column.cards[1].title, "Fix the header");
+        assert!(!column.cards[0].id.is_empty() && column.cards[0].id != column.cards[1].id);
+    }
+
+    #[test]
+    fn a_trailing_date_becomes_the_due_date() {
+        let due = NaiveDate::from_ymd_opt(2021, 12, 24);
+
+        assert_eq!(split_due_date("Ship it @2021-12-24"), (String::from("Ship it"), due));
+        assert_eq!(split_due_date("  Ship it   @ 2021-12-24 "), (String::from("Ship it"), due));
+        assert_eq!(split_due_date("Mail bob@example.com @2021-12-24"), (String::from("Mail bob@example.com"), due));
+
+        // anything else stays in the title
+        assert_eq!(split_due_date("Ship it @friday"), (String::from("Ship it @friday"), None));
+        assert_eq!(split_due_date("Ship it @2021-13-01"), (String::from("Ship it @2021-13-01"), None));
+        assert_eq!(split_due_date("@2021-12-24"), (String::from("@2021-12-24"), None));
+    }
 }
@@ -0,0 +1,186 @@
+// * Multi-line text editor state used for card descriptions
+// The cursor column counts chars, not bytes
+pub struct TextEditor {
+    pub lines: Vec<String>,
+    pub row: usize,
+    pub col: usize
+}
+
+impl TextEditor {
+
+    pub fn new(text: &str) -> TextEditor {
+        let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+
+        // start at the end of the text
+        let row = lines.len() - 1;
+        let col = lines[row].chars().count();
+
+        TextEditor {
+            lines,
+            row,
+            col
+        }
+    }
+
+    pub fn text(&self) -> String {
+
+        self.lines.join("\n")
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+
+        self.lines[row].chars().count()
+    }
+
+    // Byte offset of the cursor in the current line
+    fn byte_index(&self) -> usize {
+        let line = &self.lines[self.row];
+
+        line.char_indices().nth(self.col).map(|(i, _)| i).unwrap_or(line.len())
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let index = self.byte_index();
+
+        self.lines[self.row].insert(index, c);
+        self.col += 1;
+    }
+
+    pub fn new_line(&mut self) {
+        let index = self.byte_index();
+        let rest = self.lines[self.row].split_off(index);
+
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+    }
+
+    // Backspace, joins the line with the previous one at the start of a line
+    pub fn delete_back(&mut self) {
+
+        if self.col > 0 {
+            self.col -= 1;
+            let index = self.byte_index();
+            self.lines[self.row].remove(index);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    // Delete, joins the next line at the end of a line
+    pub fn delete_forward(&mut self) {
+
+        if self.col < self.line_len(self.row) {
+            let index = self.byte_index();
+            self.lines[self.row].remove(index);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+        }
+    }
+
+    pub fn move_right(&mut self) {
+
+        if self.col < self.line_len(self.row) {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn move_down(&mut self) {
+
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn move_home(&mut self) {
+
+        self.col = 0;
+    }
+
+    pub fn move_end(&mut self) {
+
+        self.col = self.line_len(self.row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cursor_counts_chars_in_multibyte_text() {
+        let mut editor = TextEditor::new("grüße\n日本");
+        assert_eq!((editor.row, editor.col), (1, 2));
+
+        editor.move_up();
+        assert_eq!(editor.col, 2);
+
+        // between ü and ß
+        editor.move_right();
+        editor.insert_char('✓');
+        assert_eq!(editor.text(), "grü✓ße\n日本");
+
+        editor.delete_back();
+        editor.delete_forward();
+        assert_eq!(editor.text(), "grüe\n日本");
+
+        editor.move_end();
+        assert_eq!(editor.col, 4);
+    }
+
+    #[test]
+    fn lines_are_split_and_joined_at_the_cursor() {
+        let mut editor = TextEditor::new("first second");
+
+        for _ in 0.."second".len() {
+            editor.move_left();
+        }
+
+        editor.new_line();
+        assert_eq!(editor.lines, ["first ", "second"]);
+        assert_eq!((editor.row, editor.col), (1, 0));
+
+        editor.delete_back();
+        assert_eq!(editor.text(), "first second");
+        assert_eq!((editor.row, editor.col), (0, 6));
+
+        editor.move_home();
+        editor.move_left();
+        assert_eq!((editor.row, editor.col), (0, 0));
+
+        editor.new_line();
+        editor.move_up();
+        editor.delete_forward();
+        assert_eq!(editor.text(), "first second");
+    }
+}
@@ -0,0 +1,62 @@
+use crate::data::{Card, KanbanProject};
+
+// Default file of `X` in the TUI
+pub const BOARD_EXPORT_FILE: &str = "board.md";
+
+// * Markdown export
+// For pasting into notes and pull requests, not for reading back in:
+//
+//   # Website
+//
+//   ## ToDo
+//
+//   - [ ] #3 Fix the header (due 24-12-2021)
+//
+//   ## Done
+//
+//   - [x] #1 Set up the repository
+pub fn project_to_markdown(project: &KanbanProject) -> String {
+    let mut markdown = format!("# {}\n", project.name);
+
+    for (i, column) in project.columns.iter().enumerate() {
+        markdown.push_str(&format!("\n## {}\n\n", column.name));
+
+        for card in &column.cards {
+            markdown.push_str(&card_line(card, project.is_done_column(i)));
+        }
+    }
+
+    markdown
+}
+
+pub fn board_to_markdown(projects: &[KanbanProject]) -> String {
+    let projects: Vec<String> = projects.iter().map(project_to_markdown).collect();
+
+    projects.join("\n")
+}
+
+fn card_line(card: &Card, done: bool) -> String {
+    let mut line = format!("- [{}] #{} {}", if done {"x"} else {" "}, card.number, card.title);
+
+    if let Some(due) = card.due {
+        line.push_str(&format!(" (due {})", due.format("%d-%m-%Y")));
+    }
+
+    line.push('\n');
+    line
+}
+
+// "Website Redesign" -> "website-redesign.md"
+pub fn file_name(project: &KanbanProject) -> String {
+    let slug: Vec<String> = project.name
+                            .split(|c: char| !c.is_alphanumeric())
+                            .filter(|part| !part.is_empty())
+                            .map(str::to_lowercase)
+                            .collect();
+
+    if slug.is_empty() {
+        String::from("project.md")
+    } else {
+        format!("{}.md", slug.join("-"))
+    }
+}
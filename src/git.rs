@@ -0,0 +1,359 @@
+use std::{
+    collections::HashMap,
+    env, io,
+
+    ffi::OsStr,
+
+    path::{Path, PathBuf},
+
+    process::{Command, Output, Stdio},
+
+    time::{Duration, Instant}
+};
+
+use crate::{
+    data::{KanbanProject, Task},
+    storage::{self, journal, Format}
+};
+
+pub const GIT_COMMIT_ENV: &str = "KANBAN_GIT_COMMIT";
+
+// * Automatic git commits
+// When the board is inside a git work tree, saves are committed with a message
+// saying what changed. Saves are collected until none was made for `delay`,
+// a delay of zero commits every save. Only the files of the board go into the
+// commit, whatever the user has staged stays staged and out of it. Messages of
+// an encrypted board name no card or project, git history is plain text
+pub struct AutoCommit {
+    work_tree: PathBuf,
+    // files and directories the board is kept in
+    paths: Vec<PathBuf>,
+    delay: Duration,
+    encrypted: bool,
+    // the board as it was committed last, the message says what changed since
+    committed: Snapshot,
+    // saved but not committed yet
+    saved: Option<(Snapshot, Instant)>
+}
+
+// What the commit message is made from
+struct Snapshot {
+    projects: Vec<ProjectSummary>,
+    cards: Vec<CardSummary>,
+    daily_tasks: String
+}
+
+struct ProjectSummary {
+    id: String,
+    name: String,
+    columns: String
+}
+
+struct CardSummary {
+    id: String,
+    number: u32,
+    project_id: String,
+    column: usize,
+    column_name: String,
+    title: String,
+    data: String
+}
+
+// --git-commit beats KANBAN_GIT_COMMIT, both are the seconds to wait for more changes
+pub fn commit_delay(flag: Option<u64>) -> Option<Duration> {
+
+    flag.or_else(|| env::var(GIT_COMMIT_ENV).ok()?.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+impl AutoCommit {
+
+    // Fails when the board is not inside a git work tree
+    pub fn new(board: &Path, delay: Duration, encrypted: bool, projects: &[KanbanProject], daily_tasks: &[Task]) -> io::Result<AutoCommit> {
+        let board = std::path::absolute(board)?;
+        let dir = board.parent().unwrap_or(Path::new("/"));
+
+        let work_tree = PathBuf::from(check(git(dir, ["rev-parse", "--show-toplevel"])?)?.trim());
+
+        let paths = match storage::format_of(&board) {
+            Format::Json => vec![journal::journal_path(&board), board],
+            Format::Sqlite | Format::Markdown => vec![board]
+        };
+
+        Ok(AutoCommit {
+            work_tree,
+            paths,
+            delay,
+            encrypted,
+            committed: Snapshot::of(projects, daily_tasks),
+            saved: None
+        })
+    }
+
+    pub fn saved(&mut self, projects: &[KanbanProject], daily_tasks: &[Task]) {
+
+        self.saved = Some((Snapshot::of(projects, daily_tasks), Instant::now()));
+    }
+
+    // Changes someone else made are theirs to commit
+    pub fn reloaded(&mut self, projects: &[KanbanProject], daily_tasks: &[Task]) {
+
+        if self.saved.is_none() {
+            self.committed = Snapshot::of(projects, daily_tasks);
+        }
+    }
+
+    // Commits once the last save is `delay` old, or right away with `now`
+    pub fn commit(&mut self, now: bool) -> io::Result<()> {
+
+        match &self.saved {
+            Some((_, saved_at)) if now || saved_at.elapsed() >= self.delay => (),
+            _ => return Ok(())
+        }
+
+        let (snapshot, _) = self.saved.take().unwrap();
+
+        match self.commit_snapshot(&snapshot) {
+            Ok(true) => self.committed = snapshot,
+            Ok(false) => (),
+            // e.g. another git command holds index.lock, tried again after the delay or on exit
+            Err(e) => {
+                self.saved = Some((snapshot, Instant::now()));
+
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    // False when there is nothing git could commit yet
+    fn commit_snapshot(&self, snapshot: &Snapshot) -> io::Result<bool> {
+
+        // a file that never existed and is not tracked is no pathspec git accepts
+        let paths: Vec<&PathBuf> = self.paths.iter()
+                                    .filter(|path| path.exists() || self.tracked(path))
+                                    .collect();
+
+        if paths.is_empty() {
+            return Ok(false);
+        }
+
+        check(git(&self.work_tree, args(&["add", "-A", "--"], &paths))?)?;
+
+        // e.g. a change that was undone before the delay was over
+        let staged = git(&self.work_tree, args(&["diff", "--cached", "--quiet", "--"], &paths))?;
+
+        if !staged.status.success() {
+            let message = message(&changes(&self.committed, snapshot, self.encrypted));
+
+            check(git(&self.work_tree, args(&["commit", "--only", "--no-verify", "-m", &message, "--"], &paths))?)?;
+        }
+
+        Ok(true)
+    }
+
+    fn tracked(&self, path: &Path) -> bool {
+
+        git(&self.work_tree, args(&["ls-files", "--error-unmatch", "--"], &[path]))
+            .is_ok_and(|output| output.status.success())
+    }
+}
+
+fn args<'a, P: AsRef<Path>>(args: &'a [&'a str], paths: &'a [P]) -> Vec<&'a OsStr> {
+
+    args.iter().map(OsStr::new).chain(paths.iter().map(|path| path.as_ref().as_os_str())).collect()
+}
+
+fn git<I, S>(dir: &Path, args: I) -> io::Result<Output>
+where I: IntoIterator<Item = S>, S: AsRef<OsStr> {
+
+    Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+}
+
+// stdout of a command that succeeded, else the first line git complained with
+fn check(output: Output) -> io::Result<String> {
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    Err(io::Error::other(stderr.lines().find(|line| !line.trim().is_empty()).unwrap_or("git failed").trim().to_string()))
+}
+
+impl Snapshot {
+
+    fn of(projects: &[KanbanProject], daily_tasks: &[Task]) -> Snapshot {
+        let mut snapshot = Snapshot {
+            projects: Vec::new(),
+            cards: Vec::new(),
+            daily_tasks: serde_json::to_string(daily_tasks).unwrap_or_default()
+        };
+
+        for project in projects {
+            let columns: Vec<(&String, Option<usize>, bool)> = project.columns.iter()
+                                .map(|column| (&column.name, column.wip_limit, column.sort_by_priority))
+                                .collect();
+
+            snapshot.projects.push(ProjectSummary {
+                id: project.id.clone(),
+                name: project.name.clone(),
+                columns: serde_json::to_string(&columns).unwrap_or_default()
+            });
+
+            for (i, column) in project.columns.iter().enumerate() {
+                for card in &column.cards {
+                    snapshot.cards.push(CardSummary {
+                        id: card.id.clone(),
+                        number: card.number,
+                        project_id: project.id.clone(),
+                        column: i,
+                        column_name: column.name.clone(),
+                        title: card.title.clone(),
+                        data: serde_json::to_string(card).unwrap_or_default()
+                    });
+                }
+            }
+        }
+
+        snapshot
+    }
+}
+
+// e.g. "Move 'Fix login' ToDo → In Progress", in the order of the board.
+// Without names it is "Move #3 ToDo → In Progress"
+fn changes(old: &Snapshot, new: &Snapshot, without_names: bool) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    let card_name = |card: &CardSummary| if without_names {format!("#{}", card.number)} else {format!("'{}'", card.title)};
+    let project_name = |project: &ProjectSummary| if without_names {String::from("a project")} else {format!("project '{}'", project.name)};
+
+    let old_projects: HashMap<&String, &ProjectSummary> = old.projects.iter().map(|project| (&project.id, project)).collect();
+    let new_projects: HashMap<&String, &ProjectSummary> = new.projects.iter().map(|project| (&project.id, project)).collect();
+
+    for project in &new.projects {
+        match old_projects.get(&project.id) {
+            None => changes.push(format!("Add {}", project_name(project))),
+            Some(old) => {
+                if old.name != project.name && without_names {
+                    changes.push(String::from("Rename a project"));
+                } else if old.name != project.name {
+                    changes.push(format!("Rename project '{}' → '{}'", old.name, project.name));
+                }
+                if old.columns != project.columns {
+                    changes.push(format!("Edit the columns of {}", project_name(project)));
+                }
+            }
+        }
+    }
+
+    for project in old.projects.iter().filter(|project| !new_projects.contains_key(&project.id)) {
+        changes.push(format!("Delete {}", project_name(project)));
+    }
+
+    let old_cards: HashMap<&String, &CardSummary> = old.cards.iter().map(|card| (&card.id, card)).collect();
+    let new_cards: HashMap<&String, &CardSummary> = new.cards.iter().map(|card| (&card.id, card)).collect();
+
+    for card in &new.cards {
+        match old_cards.get(&card.id) {
+            None => changes.push(format!("Add {} to {}", card_name(card), card.column_name)),
+            Some(old) if (&old.project_id, old.column) != (&card.project_id, card.column) => {
+                changes.push(format!("Move {} {} → {}", card_name(card), old.column_name, card.column_name));
+            },
+            Some(old) if old.data != card.data => changes.push(format!("Edit {}", card_name(card))),
+            Some(_) => ()
+        }
+    }
+
+    // the cards of a deleted project go with it
+    for card in old.cards.iter().filter(|card| !new_cards.contains_key(&card.id) && new_projects.contains_key(&card.project_id)) {
+        changes.push(format!("Delete {} from {}", card_name(card), card.column_name));
+    }
+
+    if old.daily_tasks != new.daily_tasks {
+        changes.push(String::from("Edit the daily tasks"));
+    }
+
+    changes
+}
+
+// The first change is the subject, all of them are listed below it when there are more
+fn message(changes: &[String]) -> String {
+
+    match changes {
+        [] => String::from("Update the board"),
+        [change] => change.clone(),
+        [first, rest @ ..] => {
+            let list: Vec<String> = changes.iter().map(|change| format!("- {}", change)).collect();
+
+            format!("{} and {} more\n\n{}", first, rest.len(), list.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn snapshot(column: usize, column_name: &str) -> Snapshot {
+
+        Snapshot {
+            projects: vec![ProjectSummary { id: String::from("p"), name: String::from("Website"), columns: String::new() }],
+            cards: vec![CardSummary {
+                id: String::from("c"),
+                number: 3,
+                project_id: String::from("p"),
+                column,
+                column_name: String::from(column_name),
+                title: String::from("Fix login"),
+                data: String::new()
+            }],
+            daily_tasks: String::new()
+        }
+    }
+
+    #[test]
+    fn messages_of_encrypted_boards_name_no_card() {
+        let old = snapshot(0, "ToDo");
+        let new = snapshot(1, "In Progress");
+
+        assert_eq!(changes(&old, &new, false), ["Move 'Fix login' ToDo → In Progress"]);
+        assert_eq!(changes(&old, &new, true), ["Move #3 ToDo → In Progress"]);
+    }
+
+    #[test]
+    fn a_failed_commit_is_made_later() {
+        let dir = std::env::temp_dir().join(format!("kanban-git-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        for config in [&["init", "-q"][..], &["config", "user.name", "kanban"], &["config", "user.email", "kanban@localhost"]] {
+            check(git(&dir, config).unwrap()).unwrap();
+        }
+
+        let board = dir.join("kanban.json");
+        fs::write(&board, "{}").unwrap();
+
+        let mut auto_commit = AutoCommit::new(&board, Duration::from_secs(0), false, &[], &[]).unwrap();
+        auto_commit.saved(&[], &[]);
+
+        // another git command is running
+        fs::write(dir.join(".git/index.lock"), "").unwrap();
+        assert!(auto_commit.commit(true).is_err());
+
+        fs::remove_file(dir.join(".git/index.lock")).unwrap();
+        auto_commit.commit(true).unwrap();
+
+        assert_eq!(check(git(&dir, ["log", "--oneline"]).unwrap()).unwrap().lines().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs, io,
+
+    fs::{File, OpenOptions, TryLockError},
+    io::{Seek, SeekFrom, Write},
+
+    path::{Path, PathBuf},
+
+    process, thread,
+
+    time::{Duration, Instant}
+};
+
+use crate::storage;
+
+// How long to wait for the other instance to hand the board over
+const TAKE_OVER_TIMEOUT: Duration = Duration::from_secs(3);
+
+// * Single writer lock
+// An advisory lock on kanban.json.lock that is held while the app runs. The
+// file holds the process id of the instance that owns the board, the OS drops
+// the lock when the process dies so a crash never leaves a stale one behind.
+// Windows lets no other handle touch a locked file, so the process id is
+// written through the locked one and another instance asks for the board in
+// kanban.json.take-over
+pub struct BoardLock {
+    file: File,
+    request: PathBuf
+}
+
+pub enum Lock {
+    Acquired(BoardLock),
+    // process id of the instance holding it, when it could be read
+    HeldBy(Option<u32>)
+}
+
+impl BoardLock {
+
+    // Another instance wrote its process id to take the board over
+    pub fn taken_over(&self) -> bool {
+
+        read_owner(&self.request).is_some_and(|owner| owner != process::id())
+    }
+}
+
+impl Drop for BoardLock {
+
+    fn drop(&mut self) {
+
+        let _ = self.file.unlock();
+    }
+}
+
+pub fn lock_path(board: &Path) -> PathBuf {
+
+    storage::sibling_path(board, ".lock")
+}
+
+fn request_path(board: &Path) -> PathBuf {
+
+    storage::sibling_path(board, ".take-over")
+}
+
+pub fn acquire(board: &Path) -> io::Result<Lock> {
+    let path = lock_path(board);
+
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+
+    match file.try_lock() {
+        Ok(()) => {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(process::id().to_string().as_bytes())?;
+
+            // a request that was answered, or whose instance is gone
+            remove_request(board)?;
+
+            Ok(Lock::Acquired(BoardLock { file, request: request_path(board) }))
+        },
+        Err(TryLockError::WouldBlock) => Ok(Lock::HeldBy(read_owner(&path))),
+        Err(TryLockError::Error(e)) => Err(e)
+    }
+}
+
+// Asks the instance holding the lock to let go, it checks for the request every second
+pub fn take_over(board: &Path) -> io::Result<Lock> {
+    let owner = read_owner(&lock_path(board));
+    let started = Instant::now();
+
+    fs::write(request_path(board), process::id().to_string())?;
+
+    loop {
+        match acquire(board)? {
+            Lock::HeldBy(_) if started.elapsed() < TAKE_OVER_TIMEOUT => thread::sleep(Duration::from_millis(100)),
+            // the owner must not give the board up later when nobody waits for it
+            Lock::HeldBy(_) => {
+                remove_request(board)?;
+
+                return Ok(Lock::HeldBy(owner));
+            },
+            acquired => return Ok(acquired)
+        }
+    }
+}
+
+fn remove_request(board: &Path) -> io::Result<()> {
+
+    match fs::remove_file(request_path(board)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(())
+    }
+}
+
+fn read_owner(path: &Path) -> Option<u32> {
+
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
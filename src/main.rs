@@ -2,11 +2,13 @@
 use std::{
     io,
 
-    time::Duration,
+    time::{Duration, Instant},
 
     fs,
 
-    path
+    process,
+
+    panic
 };
 
 // * Crossterm Modules
@@ -27,8 +29,8 @@ use tui::{
     backend::{Backend, CrosstermBackend},
 
     widgets::{Block, Borders, BorderType
-        , Tabs, List, ListItem, Paragraph
-        , Clear, Table, Row, Cell},
+        , Tabs, List, ListItem, ListState, Paragraph
+        , Clear, Wrap},
 
     layout::{Layout, Constraint, Direction, Alignment, Rect},
 
@@ -39,16 +41,126 @@ use tui::{
 
 use chrono::prelude::*;
 
-use serde_json::Result as seresult;
-
 mod data;
 use crate::data::*;
 
+mod editor;
+use crate::editor::TextEditor;
+
+mod cli;
+use crate::cli::{Args, Command, USAGE};
+
+mod paths;
+
+mod storage;
+
+mod schema;
+
+mod lock;
+use crate::lock::Lock;
+
+mod state;
+use crate::state::UiState;
+
+mod git;
+use crate::git::AutoCommit;
+
+mod export;
+
+// How often the board file is checked for changes made by other programs
+const BOARD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 fn main() -> Result<(), io::Error> {
 
+    // * Arguments, handled before the terminal is taken over
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("kanban: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if args.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    if let Some(Command::Init) = args.command {
+        return cli::init_board(args.markdown);
+    }
+
+    // Initialize app data
+    let mut app: App = App::default();
+    let (board_path, source) = paths::resolve_board_path(&args)?;
+    app.board_label = source.label(&board_path);
+    app.board_path = board_path;
+
+    if let Some(Command::Encrypt | Command::Decrypt) = args.command {
+        let encrypt = matches!(args.command, Some(Command::Encrypt));
+
+        if let Err(e) = cli::change_encryption(&app.board_path, encrypt) {
+            eprintln!("kanban: {}", e);
+            process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    if args.restore_backup {
+        return cli::restore_backup_prompt(&app.board_path);
+    }
+
+    // the passphrase is asked for before the terminal is taken over
+    app.cipher = match cli::unlock_board(&app.board_path) {
+        Ok(cipher) => cipher,
+        Err(e) => {
+            eprintln!("kanban: {}", e);
+            process::exit(1);
+        }
+    };
+    app.storage = storage::open(&app.board_path, app.cipher.clone());
+
+    if let Some(Command::History) = args.command {
+        return cli::print_history(&app.board_path, app.cipher.as_ref());
+    }
+
+    if let Some(Command::Export) = args.command {
+        if let Err(e) = cli::export(app.storage.as_mut(), args.project.as_deref(), args.output.as_deref()) {
+            eprintln!("kanban: {}", e);
+            process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    deserialize_kanban(&mut app);
+    lock_board(&mut app);
+
+    if let Some(state) = state::load(&app.board_path) {
+        state.restore(&mut app);
+    }
+
+    if let Some(delay) = git::commit_delay(args.git_commit) {
+        match AutoCommit::new(&app.board_path, delay, app.cipher.is_some(), &app.kanban.projects, &app.daily_task.tasks) {
+            Ok(auto_commit) => app.auto_commit = Some(auto_commit),
+            Err(e) => app.status = Some(format!("Changes are not committed to git: {}", e))
+        }
+    }
+
     // * Terminal Setup 
     enable_raw_mode()?;
 
+    // give the terminal back before the panic message is printed, the board
+    // lock is released when the app is dropped while unwinding
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
+
+        default_hook(info);
+    }));
+
     let mut stdout = io::stdout();
 
     // Run the app in new terminal screen
@@ -58,55 +170,112 @@ fn main() -> Result<(), io::Error> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Initialize app data
-    let mut app: App = App::default();
-    deserialize_kanban(&mut app)?;
-
     // Enter main loop function
     run_app(&mut terminal, &mut app)?;
 
+    // an unresolved recovery screen has nothing worth remembering
+    if app.recovery.is_none() {
+        state::save(&app.board_path, &UiState::capture(&app));
+    }
+
     // End of the execution
     execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
 
     disable_raw_mode()?;
 
+    // changes still waiting for the delay
+    if let Some(auto_commit) = &mut app.auto_commit {
+        if let Err(e) = auto_commit.commit(true) {
+            eprintln!("kanban: committing {} failed: {}", app.board_path.display(), e);
+        }
+    }
+
     Ok(())
 }
 
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
 
+    let mut board_checked_at = Instant::now();
+
     // * Main Loop
     loop {
+
+        // * Changes other programs made to the board
+        if board_checked_at.elapsed() >= BOARD_CHECK_INTERVAL {
+            check_lock(app);
+            check_board_changed(app);
+            commit_board(app);
+            board_checked_at = Instant::now();
+        }
+
+        // wait for the popup the user is typing into
+        if app.conflict && !app.can_input && !matches!(app.popup, Popup::EditDescription | Popup::Recovery | Popup::Conflict) {
+            open_confirm_popup(app, Popup::Conflict, String::from("r: reload theirs, o: overwrite with mine"));
+        }
+
         terminal.draw(|f| ui(f, app))?;
 
         if poll(Duration::from_millis(100))?{
             match read()?{
                 Event::Key(key) => {
+
+                    // * The description editor takes every key
+                    if let Popup::EditDescription = app.popup {
+                        handle_editor_key(app, key);
+                        continue;
+                    }
+
+                    // * So does the recovery screen of a board that could not be read
+                    if let Popup::Recovery = app.popup {
+                        if handle_recovery_key(app, key) {
+                            break;
+                        }
+                        continue;
+                    }
+
+                    if let Popup::Conflict = app.popup {
+                        handle_conflict_key(app, key);
+                        continue;
+                    }
+
+                    if let Popup::Locked = app.popup {
+                        handle_locked_key(app, key);
+                        continue;
+                    }
                     
                     // * Special keys
 
                     match key.code {
                         KeyCode::Esc => {
                             // Handle exit sequence and closing the popups
-                            if let Popup::Disabled = app.popup {
-                                break
-                            } else {
-                                close_popup(app);
+                            match app.popup {
+                                Popup::Disabled => break,
+                                // back to the checklist the item belongs to
+                                Popup::AddChecklistItem | Popup::EditChecklistItem => open_checklist(app),
+                                _ => close_popup(app)
                             }
                         },
 
                         KeyCode::Backspace => {
 
-                            if app.input.len() > 0 && app.can_input{
+                            if !app.input.is_empty() && app.can_input{
                                 app.input.pop();
                             }
                         },
 
                         KeyCode::Delete => {
 
-                            if key.modifiers == KeyModifiers::CONTROL {
+                            if let Popup::Checklist = app.popup {
+                                // delete the selected checklist item
+                                let index = app.kanban.checklist_index;
+
+                                if app.kanban.selected_card_mut().map(|card| card.remove_checklist_item(index)).unwrap_or(false) {
+                                    clamp_checklist_index(app);
+                                    serialize_kanban(app);
+                                }
+                            } else if key.modifiers == KeyModifiers::CONTROL {
                                 // delete kanban project
-                                if app.kanban.projects.len() > 0{
+                                if !app.kanban.projects.is_empty(){
                                     open_delete_popup(app, Popup::DeleteProject, app.kanban.projects[app.kanban.project_index].name.clone());
                                 }
                             } else {
@@ -121,30 +290,15 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                     // kanban
 
                                     // "projects" is not empty
-                                    if app.kanban.projects.len() > 0 {
+                                    if !app.kanban.projects.is_empty() {
 
-                                        if app.focus.chunk_focus[3] == 0 {
-                                            // to do
-                                            if app.kanban.projects[app.kanban.project_index].todo.len() > 0 {
-                                                open_delete_popup(app, Popup::DeleteTodo, 
-                                                    app.kanban.projects[app.kanban.project_index].todo[app.kanban.todo_index].clone());
-                                            }
-                                            
-                                        }else if app.focus.chunk_focus[3] == 1 {
-                                            // in progress
-        
-                                            if app.kanban.projects[app.kanban.project_index].in_progress.len() > 0 {
-                                                open_delete_popup(app, Popup::DeleteInProgress, 
-                                                    app.kanban.projects[app.kanban.project_index].in_progress[app.kanban.in_progress_index].clone());
-                                            }
-                                            
-                                        }else if app.focus.chunk_focus[3] == 2 {
-                                            // done
-                                            
-                                            if  app.kanban.projects[app.kanban.project_index].done.len() > 0 {
-                                                open_delete_popup(app, Popup::DeleteDone, 
-                                                    app.kanban.projects[app.kanban.project_index].done[app.kanban.done_index].clone());
+                                        if key.modifiers == KeyModifiers::SHIFT {
+                                            // delete the focused column
+                                            if app.kanban.project().columns.len() > 1 {
+                                                open_delete_popup(app, Popup::DeleteColumn, app.kanban.column().name.clone());
                                             }
+                                        } else if let Some(card) = app.kanban.selected_card() {
+                                            open_delete_popup(app, Popup::DeleteCard, card.title.clone());
                                         }
                                     }
                                 }
@@ -176,24 +330,13 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                         }
                                     },
     
-                                    Popup::AddTodo => {
-                                        if ! app.input.is_empty() {
-                                            app.kanban.add_todo(app.kanban.project_index, &app.input);
-        
-                                            // close the popup
-                                            close_popup(app);
-        
-                                            // update the json file for kanban
-                                            serialize_kanban(app);
-                                        }else {
-                                            // input is empty -> close the popup
-                                            close_popup(app);
-                                        }
-                                    },
-    
-                                    Popup::AddInProgress => {
-                                        if ! app.input.is_empty() {
-                                            app.kanban.add_in_progress(app.kanban.project_index, &app.input);
+                                    Popup::AddCard => {
+                                        let (title, due) = split_due_date(&app.input);
+
+                                        if ! title.is_empty() {
+                                            let mut card = Card::new(&title);
+                                            card.due = due;
+                                            app.kanban.add_card(card);
         
                                             // close the popup
                                             close_popup(app);
@@ -205,15 +348,13 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                             close_popup(app);
                                         }
                                     },
-    
-                                    Popup::AddDone => {
+
+                                    Popup::AddColumn => {
                                         if ! app.input.is_empty() {
-                                            app.kanban.add_done(app.kanban.project_index, &app.input);
-        
-                                            // close the popup
+                                            app.kanban.add_column(&app.input);
+
                                             close_popup(app);
-        
-                                            // update the json file for kanban
+
                                             serialize_kanban(app);
                                         }else {
                                             // input is empty -> close the popup
@@ -234,10 +375,14 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                         }
                                     },
     
-                                    Popup::EditTodo => {
-                                        if ! app.input.is_empty() {
-                                            app.kanban.projects[app.kanban.project_index]
-                                                .todo[app.kanban.todo_index] = app.input.to_string();
+                                    Popup::EditCard => {
+                                        let (title, due) = split_due_date(&app.input);
+
+                                        if ! title.is_empty() {
+                                            if let Some(card) = app.kanban.selected_card_mut() {
+                                                card.set_title(&title);
+                                                card.set_due(due);
+                                            }
     
                                             close_popup(app);
     
@@ -248,13 +393,12 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                         }
                                     },
 
-                                    Popup::EditInProgress => {
+                                    Popup::RenameColumn => {
                                         if ! app.input.is_empty() {
-                                            app.kanban.projects[app.kanban.project_index]
-                                                .in_progress[app.kanban.in_progress_index] = app.input.to_string();
-    
+                                            app.kanban.rename_column(&app.input);
+
                                             close_popup(app);
-    
+
                                             serialize_kanban(app);
                                         } else {
                                             // input is empty -> old name
@@ -262,34 +406,127 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                         }
                                     },
 
-                                    Popup::EditDone => {
-                                        if ! app.input.is_empty() {
-                                            app.kanban.projects[app.kanban.project_index]
-                                                .done[app.kanban.done_index] = app.input.to_string();
-    
+                                    Popup::SetWipLimit => {
+                                        // empty input or 0 removes the limit, anything else is ignored
+                                        if app.input.is_empty() {
+                                            app.kanban.column_mut().wip_limit = None;
+                                        } else if let Ok(limit) = app.input.trim().parse::<usize>() {
+                                            app.kanban.column_mut().wip_limit = if limit == 0 {None} else {Some(limit)};
+                                        }
+
+                                        close_popup(app);
+                                        serialize_kanban(app);
+                                    },
+
+                                    Popup::ConfirmMove => {
+                                        if let Some(to_column) = app.kanban.pending_move.take() {
+                                            app.kanban.move_card(to_column);
+                                        }
+
+                                        close_popup(app);
+                                        serialize_kanban(app);
+                                    },
+
+                                    Popup::AddLabel => {
+                                        // "name" or "name:colour"
+                                        let (label, color) = match app.input.split_once(':') {
+                                            Some((label, color)) => (label.trim().to_string(), color_from_name(color)),
+                                            None => (app.input.trim().to_string(), None)
+                                        };
+
+                                        if !label.is_empty() && app.kanban.selected_card().is_some() {
+                                            app.kanban.define_label(&label, color);
+
+                                            if let Some(card) = app.kanban.selected_card_mut() {
+                                                card.add_label(&label);
+                                            }
+
                                             close_popup(app);
-    
                                             serialize_kanban(app);
                                         } else {
-                                            // input is empty -> old name
                                             close_popup(app);
                                         }
                                     },
 
-                                    Popup::DeleteTodo => {
-                                        delete_todo(app);
+                                    Popup::RemoveLabel => {
+                                        let label = app.input.trim().to_string();
+
+                                        let removed = app.kanban.selected_card_mut()
+                                            .map(|card| card.remove_label(&label))
+                                            .unwrap_or(false);
+
+                                        close_popup(app);
+
+                                        if removed {
+                                            serialize_kanban(app);
+                                        }
+                                    },
+
+                                    Popup::Checklist => {
+                                        // enter toggles like space
+                                        toggle_checklist_item(app);
+                                    },
+
+                                    Popup::AddChecklistItem => {
+                                        if ! app.input.trim().is_empty() {
+                                            let name = app.input.trim().to_string();
+
+                                            if let Some(card) = app.kanban.selected_card_mut() {
+                                                card.add_checklist_item(&name);
+                                                app.kanban.checklist_index = card.checklist.len() - 1;
+                                            }
+
+                                            serialize_kanban(app);
+                                        }
+
+                                        open_checklist(app);
+                                    },
+
+                                    Popup::EditChecklistItem => {
+                                        if ! app.input.trim().is_empty() {
+                                            let name = app.input.trim().to_string();
+                                            let index = app.kanban.checklist_index;
+
+                                            if app.kanban.selected_card_mut().map(|card| card.rename_checklist_item(index, &name)).unwrap_or(false) {
+                                                serialize_kanban(app);
+                                            }
+                                        }
+
+                                        open_checklist(app);
+                                    },
+
+                                    Popup::Disabled if kanban_has_focus(app) && app.kanban.selected_card().is_some() => {
+                                        // details of the selected task
+                                        app.kanban.detail_scroll = 0;
+                                        app.popup = Popup::CardDetail;
+                                    },
+
+                                    Popup::CardDetail => close_popup(app),
+
+                                    Popup::GoToCard => {
+                                        // "#42" or "42"
+                                        let number = app.input.trim().trim_start_matches('#').parse::<u32>();
+
+                                        if let Ok(number) = number {
+                                            app.kanban.select_card_by_number(number);
+                                        }
+
+                                        close_popup(app);
+                                    },
+
+                                    Popup::ExportProject | Popup::ExportBoard => {
+                                        export_markdown(app);
                                         close_popup(app);
-                                        serialize_kanban(app);
                                     },
 
-                                    Popup::DeleteInProgress => {
-                                        delete_in_progress(app);
+                                    Popup::DeleteCard => {
+                                        app.kanban.delete_card();
                                         close_popup(app);
                                         serialize_kanban(app);
                                     },
 
-                                    Popup::DeleteDone => {
-                                        delete_done(app);
+                                    Popup::DeleteColumn => {
+                                        app.kanban.delete_column();
                                         close_popup(app);
                                         serialize_kanban(app);
                                     },
@@ -307,6 +544,7 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                             app.kanban.projects.remove(app.kanban.project_index);
                                         }
 
+                                        app.kanban.reset_selection();
                                         close_popup(app);
                                         serialize_kanban(app);
                                     },
@@ -347,71 +585,63 @@ fn kanban_ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let tabs = tab_bar(app);
     f.render_widget(tabs, top_chunks[0]);
 
+    // Which board is open and whether it can be written
+    let mut board = vec![Span::styled(format!("{} ", app.board_label), Style::default().fg(Color::DarkGray))];
+
+    if app.read_only {
+        let text = if app.locked_out {"read-only, open in another instance "} else {"read-only, changes are not saved "};
+        board.push(Span::styled(text, Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+    }
+
+    let board = Paragraph::new(Spans::from(board))
+    .alignment(Alignment::Right);
+
+    f.render_widget(board, top_chunks[0]);
+
     // Date bar init and render
-    let date_bar = date_bar();
+    let date_bar = date_bar(app);
     for bar in date_bar{
         f.render_widget(bar, top_chunks[1]);
     }
 
-    let body_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(
-                [
-                    Constraint::Ratio(1,3),
-                    Constraint::Ratio(1,3),
-                    Constraint::Ratio(1,3),
-                ].as_ref()
-            ).split(top_chunks[3]);
-    
     // There is at least one project
-    if app.kanban.projects.len() > 0{
+    if !app.kanban.projects.is_empty(){
         // Project name
-        let project_name = Paragraph::new(Span::from(&app.kanban.projects[app.kanban.project_index].name[..]))
+        let project_name = Paragraph::new(Span::from(&app.kanban.project().name[..]))
         .block(Block::default())
         .alignment(Alignment::Center)
         .style(Style::default().bg(Color::Cyan).fg(Color::Black).add_modifier(Modifier::BOLD));
 
         f.render_widget(project_name, top_chunks[2]);
 
-        let todo = &app.kanban.projects[app.kanban.project_index].todo;
-        let todo: Vec<ListItem> = strings_to_listitem_vec(&todo) ;
-        let todo = List::new(todo)
-                                .block(Block::default()
-                                .title(Span::styled("  ToDo  ", if app.focus.chunk_focus[3] == 0 {
-                                    Style::default().bg(Color::Cyan).fg(Color::Black)
-                                }else {
-                                    Style::default()
-                                }))
-                                .title_alignment(Alignment::Center)
-                                .borders(Borders::ALL)
-                                .border_type(BorderType::Rounded)
-                            )
-                            .style(Style::default().fg(Color::White))
-                            .highlight_style(Style::default().bg(Color::Yellow).fg(Color::Black))
-                            .highlight_symbol(" ❱ ");
+        // One equally wide chunk per column
+        let column_count = app.kanban.project().columns.len();
+        let constraints = vec![Constraint::Ratio(1, column_count as u32); column_count];
 
-        let in_progress = &app.kanban.projects[app.kanban.project_index].in_progress;
-        let in_progress: Vec<ListItem> = strings_to_listitem_vec(&in_progress) ;
-        let in_progress = List::new(in_progress)
-                                .block(Block::default()
-                                .title(Span::styled("  In Progress  ", if app.focus.chunk_focus[3] == 1 {
-                                    Style::default().bg(Color::Cyan).fg(Color::Black)
-                                }else {
-                                    Style::default()
-                                }))
-                                .title_alignment(Alignment::Center)
-                                .borders(Borders::ALL)
-                                .border_type(BorderType::Rounded)
-                            )
-                            .style(Style::default().fg(Color::White))
-                            .highlight_style(Style::default().bg(Color::Yellow).fg(Color::Black))
-                            .highlight_symbol(" ❱ ");
+        let body_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(constraints)
+                .split(top_chunks[3]);
+
+        app.kanban.column_states.resize_with(column_count, ListState::default);
+
+        let project = &app.kanban.projects[app.kanban.project_index];
+
+        for (i, column) in project.columns.iter().enumerate() {
+            let mut title = match column.wip_limit {
+                Some(limit) => format!("  {} {}/{}", column.name, column.cards.len(), limit),
+                None => format!("  {}", column.name)
+            };
 
-        let done = &app.kanban.projects[app.kanban.project_index].done;
-        let done: Vec<ListItem> = strings_to_listitem_vec(&done) ;
-        let done = List::new(done)
+            // sorted columns are marked with an arrow
+            title.push_str(if column.sort_by_priority {" ⇣  "} else {"  "});
+
+            let cards: Vec<ListItem> = cards_to_listitem_vec(&column.cards, &app.kanban, project.is_done_column(i));
+            let cards = List::new(cards)
                                 .block(Block::default()
-                                .title(Span::styled("  Done  ", if app.focus.chunk_focus[3] == 2 {
+                                .title(Span::styled(title, if column.is_over_limit() {
+                                    Style::default().bg(Color::Red).fg(Color::Black)
+                                }else if app.kanban.column_index == i {
                                     Style::default().bg(Color::Cyan).fg(Color::Black)
                                 }else {
                                     Style::default()
@@ -424,13 +654,12 @@ fn kanban_ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
                             .highlight_style(Style::default().bg(Color::Yellow).fg(Color::Black))
                             .highlight_symbol(" ❱ ");
 
-        app.kanban.todo_state.select(Option::from(app.kanban.todo_index));
-        app.kanban.in_progress_state.select(Option::from(app.kanban.in_progress_index));
-        app.kanban.done_state.select(Option::from(app.kanban.done_index));
+            // only the focused column shows a selection
+            let state = &mut app.kanban.column_states[i];
+            state.select(if app.kanban.column_index == i {Some(app.kanban.card_index)} else {None});
 
-        f.render_stateful_widget(todo, body_chunks[0], &mut app.kanban.todo_state);
-        f.render_stateful_widget(in_progress, body_chunks[1], &mut app.kanban.in_progress_state);
-        f.render_stateful_widget(done, body_chunks[2], &mut app.kanban.done_state);
+            f.render_stateful_widget(cards, body_chunks[i], state);
+        }
     }else {
         //there is not any project
 
@@ -441,20 +670,57 @@ fn kanban_ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
         f.render_widget(project_name, top_chunks[2]);
     }
+
+    // Failed saves replace the project name until the next save works
+    if let Some(status) = &app.status {
+        let status = Paragraph::new(Span::from(&status[..]))
+        .block(Block::default())
+        .alignment(Alignment::Center)
+        .style(Style::default().bg(Color::Red).fg(Color::White).add_modifier(Modifier::BOLD));
+
+        f.render_widget(status, top_chunks[2]);
+    }
     
     // Popup rendering
     match app.popup {
         Popup::AddProject => show_popup(f, app, "Add a New Project", Color::Cyan),
-        Popup::AddTodo => show_popup(f, app, "Add a New Todo Task", Color::Yellow),
-        Popup::AddInProgress => show_popup(f, app, "Add a new In Progress Task", Color::Yellow),
-        Popup::AddDone => show_popup(f, app, "Add a New Done Task", Color::Yellow),
+        Popup::AddCard => {
+            let title = format!("Add a New {} Task (@YYYY-MM-DD for a due date)", app.kanban.column().name);
+            show_popup(f, app, &title, Color::Yellow)
+        },
         Popup::EditProject => show_popup(f, app, "Edit Project Name", Color::Cyan),
-        Popup::EditTodo => show_popup(f, app, "Edit Todo Task's Name", Color::Yellow),
-        Popup::EditInProgress => show_popup(f, app, "Edit Todo Task's Name", Color::Yellow),
-        Popup::EditDone => show_popup(f, app, "Edit Done Task's Name", Color::Yellow),
-        Popup::DeleteTodo => show_popup(f, app, "Delete Todo?", Color::Red),
-        Popup::DeleteInProgress => show_popup(f, app, "Delete In Progress?", Color::Red),
-        Popup::DeleteDone => show_popup(f, app, "Delete Done?", Color::Red),
+        Popup::EditCard => {
+            let title = format!("Edit {} Task's Name", app.kanban.column().name);
+            show_popup(f, app, &title, Color::Yellow)
+        },
+        Popup::DeleteCard => {
+            let title = format!("Delete {}?", app.kanban.column().name);
+            show_popup(f, app, &title, Color::Red)
+        },
+        Popup::AddColumn => show_popup(f, app, "Add a New Column", Color::Cyan),
+        Popup::RenameColumn => show_popup(f, app, "Rename Column", Color::Cyan),
+        Popup::DeleteColumn => show_popup(f, app, "Delete Column and Its Tasks?", Color::Red),
+        Popup::SetWipLimit => show_popup(f, app, "Set WIP Limit (empty for none)", Color::Cyan),
+        Popup::ConfirmMove => show_popup(f, app, "Move Anyway?", Color::Red),
+        Popup::Checklist => show_checklist(f, app),
+        Popup::CardDetail => show_card_detail(f, app),
+        Popup::Recovery => show_recovery(f, app),
+        Popup::Conflict => show_popup(f, app, "Board Changed On Disk", Color::Red),
+        Popup::Locked => show_popup(f, app, "Board Is Open Elsewhere", Color::Yellow),
+        Popup::EditDescription => show_description_editor(f, app),
+        Popup::AddChecklistItem => {
+            show_checklist(f, app);
+            show_popup(f, app, "Add a Checklist Item", Color::Green)
+        },
+        Popup::EditChecklistItem => {
+            show_checklist(f, app);
+            show_popup(f, app, "Edit Checklist Item", Color::Green)
+        },
+        Popup::GoToCard => show_popup(f, app, "Go to Task #", Color::Cyan),
+        Popup::ExportProject => show_popup(f, app, "Export Project to Markdown File", Color::Cyan),
+        Popup::ExportBoard => show_popup(f, app, "Export All Projects to Markdown File", Color::Cyan),
+        Popup::AddLabel => show_popup(f, app, "Add Label (name or name:colour)", Color::Magenta),
+        Popup::RemoveLabel => show_popup(f, app, "Remove Label", Color::Magenta),
         Popup::DeleteProject => show_popup(f, app, "Delete Current Project?", Color::Red),
         _ => ()
     }
@@ -489,7 +755,7 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-fn tab_bar(app: &mut App) -> Tabs {
+fn tab_bar(app: &mut App) -> Tabs<'_> {
     let titles = app.tab.values().cloned().map(Spans::from).collect();
     Tabs::new(titles)
         .block(Block::default())
@@ -499,11 +765,54 @@ fn tab_bar(app: &mut App) -> Tabs {
         .select(0)
 }
 
-fn strings_to_listitem_vec(strings: &Vec<String>) -> Vec<ListItem> {
+fn cards_to_listitem_vec<'a>(cards: &'a [Card], kanban: &Kanban, done: bool) -> Vec<ListItem<'a>> {
     let mut items: Vec<ListItem> = Vec::new(); 
+    let today = Local::now().date_naive();
+
+    for card in cards {
+        let mut spans = vec![
+            Span::styled(format!("#{} ", card.number), Style::default().fg(Color::DarkGray)),
+            Span::styled(card.priority.glyph(), Style::default().fg(card.priority.color()).add_modifier(Modifier::BOLD)),
+            Span::raw(&card.title[..])
+        ];
+
+        // checklist progress
+        if let Some((checked, total)) = card.checklist_progress() {
+            spans.push(Span::styled(format!("  {}/{}", checked, total),
+                if checked == total {Style::default().fg(Color::Green)} else {Style::default().fg(Color::Gray)}));
+        }
+
+        // due date, highlighted when the card slipped or is due today
+        if let Some(due) = card.due {
+            let style = if done {
+                Style::default().fg(Color::DarkGray)
+            } else if card.is_overdue(today) {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else if card.is_due_today(today) {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            spans.push(Span::styled(due.format("  %d-%m-%Y").to_string(), style));
+        }
+
+        // labels as coloured tags after the title
+        for label in &card.labels {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(format!(" {} ", label),
+                Style::default().bg(kanban.label_color(label)).fg(Color::Black)));
+        }
+
+        let item = ListItem::new(Spans::from(spans));
 
-    for s in strings {
-        items.push(ListItem::new(&s[..]));
+        items.push(if !done && card.is_overdue(today) {
+            item.style(Style::default().fg(Color::Red))
+        } else if !done && card.is_due_today(today) {
+            item.style(Style::default().fg(Color::Yellow))
+        } else {
+            item
+        });
     }
 
     items
@@ -523,10 +832,18 @@ fn kanban_top_chunks_init<B: Backend>(f: &mut Frame<B>) -> Vec<Rect> {
             ).split(f.size())
 }
 
-fn date_bar<'a>() -> Vec<Paragraph<'a>>{
+fn date_bar<'a>(app: &App) -> Vec<Paragraph<'a>>{
+    let overdue = app.kanban.overdue_count(Local::now().date_naive());
+
     vec![
-        Paragraph::new(Span::raw(
-            Local::now().format("    %d-%m-%Y").to_string()))
+        Paragraph::new(Spans::from(vec![
+            Span::raw(Local::now().format("    %d-%m-%Y").to_string()),
+            if overdue > 0 {
+                Span::styled(format!("  {} overdue", overdue), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+            } else {
+                Span::raw("")
+            }
+        ]))
             .block(Block::default().borders(Borders::TOP | Borders::BOTTOM))
             .alignment(Alignment::Left)
             .style(Style::default().fg(Color::White)),
@@ -551,6 +868,18 @@ fn handle_keyboard(app: &mut App, key: KeyEvent){
     let selected_task_index = app.daily_task.selected_task_index;
     let selected_step_index = app.daily_task.selected_step_index;
 
+    // * Checklist popup has its own selection
+    if let Popup::Checklist = app.popup {
+        handle_checklist_keys(app, key);
+        return;
+    }
+
+    // * Task details scroll and open the editors
+    if let Popup::CardDetail = app.popup {
+        handle_detail_keys(app, key);
+        return;
+    }
+
     // * Arrow keys
     match key.code {
 
@@ -565,28 +894,18 @@ fn handle_keyboard(app: &mut App, key: KeyEvent){
 
                 }else if key.modifiers == KeyModifiers::SHIFT {
                     // * Kanban
-                    if tab_focus == 3 && app.focus.chunk_focus[3] == 1 {
-
-                        // * todo <- in_progress - done
-                        if app.kanban.projects[app.kanban.project_index].in_progress.len() > 0{
-
-                            app.kanban.add_todo(app.kanban.project_index, 
-                                &app.kanban.projects[app.kanban.project_index].in_progress[app.kanban.in_progress_index].clone());
-
-                            delete_in_progress(app);
-                            serialize_kanban(app);
-                        }
-                    }else if tab_focus == 3 && app.focus.chunk_focus[3] == 2 {
-
-                        // * todo - in_progress <- done 
+                    // move the selected task one column to the left
+                    if tab_focus == 3 && !app.kanban.projects.is_empty() && app.kanban.column_index > 0 {
+                        request_card_move(app, app.kanban.column_index - 1);
+                    }
 
-                        if app.kanban.projects[app.kanban.project_index].done.len() > 0 {
-                            app.kanban.add_in_progress(app.kanban.project_index, 
-                                &app.kanban.projects[app.kanban.project_index].done[app.kanban.done_index].clone());
+                }else if key.modifiers == KeyModifiers::ALT {
+                    // * Kanban
+                    // move the focused column one place to the left
+                    if tab_focus == 3 && !app.kanban.projects.is_empty() && app.kanban.column_index > 0
+                        && app.kanban.move_column(app.kanban.column_index - 1) {
 
-                            delete_done(app);
-                            serialize_kanban(app);
-                        }
+                        serialize_kanban(app);
                     }
 
                 }else{
@@ -598,23 +917,16 @@ fn handle_keyboard(app: &mut App, key: KeyEvent){
                         app.daily_task.selected_step_index = 1000;
                     }
 
-                    // * Kanban
-                    if tab_focus == 3 && app.focus.chunk_focus[3] == 1 {
-
-                        // * todo <- in_progress - done
-                        app.kanban.todo_index = 0;
-                        app.kanban.in_progress_index = 1000;
-
-                    }else if tab_focus == 3 && app.focus.chunk_focus[3] == 2 {
-
-                        // * todo - in_progress <- done 
-                        app.kanban.in_progress_index = 0;
-                        app.kanban.done_index = 1000;
+                    if tab_focus == 3 {
+                        // * Kanban
+                        if app.kanban.column_index > 0 {
+                            app.kanban.column_index -= 1;
+                            app.kanban.card_index = 0;
+                        }
+                    }else {
+                        // change chunk focus
+                        app.focus.chunk_focus[tab_focus as usize] = chunk_focus[tab_focus as usize] - (chunk_focus[tab_focus as usize] > 0) as i32;
                     }
-
-                    // change chunk focus
-                    app.focus.chunk_focus[tab_focus as usize] = chunk_focus[tab_focus as usize] - 1 * (chunk_focus[tab_focus as usize] > 0) as i32;
-
                 }
             }
         },
@@ -629,27 +941,21 @@ fn handle_keyboard(app: &mut App, key: KeyEvent){
 
                 } else if key.modifiers == KeyModifiers::SHIFT {
                     // * Kanban
-                    if tab_focus == 3 && app.focus.chunk_focus[3] == 0 {
-                        // * todo -> in_progress - done
-                        if app.kanban.projects[app.kanban.project_index].todo.len() > 0 {
-                            app.kanban.add_in_progress(app.kanban.project_index, 
-                                &app.kanban.projects[app.kanban.project_index].todo[app.kanban.todo_index].clone());
-                                
-                            delete_todo(app);
-                            serialize_kanban(app);
-                        }
-                    }else if tab_focus == 3 && app.focus.chunk_focus[3] == 1 {
-                        // * todo - in_progress -> done 
-
-                        if app.kanban.projects[app.kanban.project_index].in_progress.len() > 0 {
-                            app.kanban.add_done(app.kanban.project_index, 
-                                &app.kanban.projects[app.kanban.project_index].in_progress[app.kanban.in_progress_index].clone());
-                            
-                            delete_in_progress(app);
-                            serialize_kanban(app);
-                        }
+                    // move the selected task one column to the right
+                    if tab_focus == 3 && !app.kanban.projects.is_empty() {
+                        request_card_move(app, app.kanban.column_index + 1);
+                    }
+
+                } else if key.modifiers == KeyModifiers::ALT {
+                    // * Kanban
+                    // move the focused column one place to the right
+                    if tab_focus == 3 && !app.kanban.projects.is_empty()
+                        && app.kanban.move_column(app.kanban.column_index + 1) {
+
+                        serialize_kanban(app);
                     }
-                } else { 
+
+                } else {
 
                     // * Daily Task
                     // step selection for visual cue
@@ -659,24 +965,18 @@ fn handle_keyboard(app: &mut App, key: KeyEvent){
                         app.daily_task.selected_step_index = 0;
                     }
 
-                    // * Kanban
-                    if tab_focus == 3 && app.focus.chunk_focus[3] == 0 {
-
-                        // * todo -> in_progress - done
-                        app.kanban.todo_index = 1000;
-                        app.kanban.in_progress_index = 0;
-
-                    }else if tab_focus == 3 && app.focus.chunk_focus[3] == 1 {
-                        // * todo - in_progress -> done 
-                        app.kanban.in_progress_index = 1000;
-                        app.kanban.done_index = 0;
+                    if tab_focus == 3 {
+                        // * Kanban
+                        if !app.kanban.projects.is_empty() && app.kanban.column_index + 1 < app.kanban.project().columns.len() {
+                            app.kanban.column_index += 1;
+                            app.kanban.card_index = 0;
+                        }
+                    }else {
+                        // change chunk focus
+                        app.focus.chunk_focus[tab_focus as usize] =
+                            if app.focus.chunk_focus[tab_focus as usize] + 1 >= app.chunk_size[tab_focus as usize] {app.chunk_size[tab_focus as usize]}
+                                else {chunk_focus[tab_focus as usize] + 1};
                     }
-
-                    // change chunk focus
-                    app.focus.chunk_focus[tab_focus as usize] = 
-                        if app.focus.chunk_focus[tab_focus as usize] + 1 >= app.chunk_size[tab_focus as usize] {app.chunk_size[tab_focus as usize]}
-                            else {chunk_focus[tab_focus as usize] + 1};
-                    
                 }
             }
         },
@@ -686,51 +986,40 @@ fn handle_keyboard(app: &mut App, key: KeyEvent){
             if let Popup::Disabled = app.popup{
                 if key.modifiers == KeyModifiers::CONTROL{
                     // Kanban project up
-                    if app.kanban.projects.len() > 0 && tab_focus == 3{
+                    if !app.kanban.projects.is_empty() && tab_focus == 3{
                         app.kanban.project_index = if app.kanban.project_index + 1 < app.kanban.projects.len(){
                                                         app.kanban.project_index + 1
                                                     }else {
                                                         app.kanban.projects.len() - 1
                                                     };
-    
-                        adjust_kanban_indexes_upon_project_change(app);
-    
+
+                        app.kanban.reset_selection();
+
                     }
                 }else{
                     // * Daily Task
                     if tab_focus == 1 && chunk_focus[1] == 0 {
-    
+
                         // focus is on the tasks
-                        app.daily_task.selected_task_index = 
-                            if selected_task_index <= 0 {0} 
+                        app.daily_task.selected_task_index =
+                            if selected_task_index == 0 {0}
                                 else {selected_task_index - 1};
-    
+
                     } else if tab_focus == 1 && chunk_focus[1] == 1 {
-                        
+
                         // focus is on the steps
                         app.daily_task.selected_step_index =
-                            if selected_step_index <= 0 {0}
+                            if selected_step_index == 0 {0}
                                 else {selected_step_index - 1};
-                        
+
                         app.daily_task.daily_task_step_list_state.select(Option::from(selected_step_index));
                     }
-    
+
                     // * Kanban
-    
-                    if tab_focus == 3 && chunk_focus[3] == 0 {
-    
-                        // * todo up
-                        app.kanban.todo_index = if app.kanban.todo_index <= 0 {0} else {app.kanban.todo_index - 1};
-    
-                    }else if tab_focus == 3 && chunk_focus[3] == 1 {
-    
-                        // * in progress up
-                        app.kanban.in_progress_index = if app.kanban.in_progress_index <= 0 {0} else {app.kanban.in_progress_index - 1};
-    
-                    }else if tab_focus == 3 && chunk_focus[3] == 2 {
-    
-                        // * done up
-                        app.kanban.done_index = if app.kanban.done_index <= 0 {0} else {app.kanban.done_index - 1};
+                    if tab_focus == 3 {
+
+                        // * task up
+                        app.kanban.card_index = app.kanban.card_index.saturating_sub(1);
                     }
                 }
             }
@@ -741,78 +1030,53 @@ fn handle_keyboard(app: &mut App, key: KeyEvent){
             if let Popup::Disabled = app.popup{
                 if key.modifiers == KeyModifiers::CONTROL{
                     // Kanban project down
-                    if app.kanban.projects.len() > 0 && tab_focus == 3 {
+                    if !app.kanban.projects.is_empty() && tab_focus == 3 {
                         app.kanban.project_index = if app.kanban.project_index as i32 - 1 <= 0 {
                                                         0
                                                     }else {
                                                         app.kanban.project_index - 1
                                                     };
-    
-                        adjust_kanban_indexes_upon_project_change(app);
+
+                        app.kanban.reset_selection();
                     }
                 }else {
                     // * Daily Task
-    
+
                     if tab_focus == 1 && chunk_focus[1] == 0 {
-    
+
                         // focus is on the tasks
-                        app.daily_task.selected_task_index = 
+                        app.daily_task.selected_task_index =
                             if selected_task_index + 1 >= app.daily_task.tasks.len()
-                                {app.daily_task.tasks.len() - 1} 
+                                {app.daily_task.tasks.len() - 1}
                                     else {selected_task_index + 1};
-    
+
                         app.daily_task.daily_task_step_list_state.select(Option::from(1000));
-    
+
                     } else if tab_focus == 1 && chunk_focus[1] == 1 {
-                        
+
                         // focus is on the steps
                         app.daily_task.selected_step_index =
                             if selected_step_index + 1 >= app.daily_task.tasks[selected_task_index].steps.len()
                                 {app.daily_task.tasks[selected_task_index].steps.len() - 1}
                                     else {selected_step_index + 1};
-    
+
                         app.daily_task.daily_task_step_list_state.select(Option::from(app.daily_task.selected_step_index));
                     }
-    
-                    // * Kanban  
-    
-                    if tab_focus == 3 && chunk_focus[3] == 0 {
-    
-                        // * todo down
-                        if app.kanban.projects[app.kanban.project_index].todo.len() > 0 {
-                            app.kanban.todo_index = 
-                                if app.kanban.todo_index + 1 >= app.kanban.projects[app.kanban.project_index].todo.len() {
-                                    app.kanban.projects[app.kanban.project_index].todo.len() - 1
-                                } else {app.kanban.todo_index + 1};
-                        }
-                    }else if tab_focus == 3 && chunk_focus[3] == 1 {
-    
-                        // * in progress down
-                        if app.kanban.projects[app.kanban.project_index].in_progress.len() > 0 {
-                            app.kanban.in_progress_index = 
-                                if app.kanban.in_progress_index + 1 >= app.kanban.projects[app.kanban.project_index].in_progress.len(){
-                                    app.kanban.projects[app.kanban.project_index].in_progress.len() - 1
-                                } else {app.kanban.in_progress_index + 1};
-                        }
-                        
-    
-                    }else if tab_focus == 3 && chunk_focus[3] == 2 {
-    
-                        // * done down
-                        if app.kanban.projects[app.kanban.project_index].done.len() > 0 {
-                            app.kanban.done_index = 
-                                if app.kanban.done_index + 1 >= app.kanban.projects[app.kanban.project_index].done.len() {
-                                    app.kanban.projects[app.kanban.project_index].done.len() - 1
-                                } else {app.kanban.done_index + 1};
+
+                    // * Kanban
+                    if tab_focus == 3 && !app.kanban.projects.is_empty() {
+
+                        // * task down
+                        if app.kanban.card_index + 1 < app.kanban.column().cards.len() {
+                            app.kanban.card_index += 1;
                         }
-                        
                     }
                 }
             }
         },
         _ => handle_input(app, key)
     }
-                
+
 }
 
 // TODO here
@@ -820,24 +1084,54 @@ fn handle_input(app: &mut App, key: KeyEvent) {
 
     // * char keys
 
-    match key.code {
-        KeyCode::Char(c) => {
-            if c == 'n' {
+    if let KeyCode::Char(c) = key.code {
+        if c == 'n' {
 
-                handle_n_key(key, app);
-                
-            } else if c == 'e' {
+            handle_n_key(key, app);
+            
+        } else if c == 'e' {
 
-                handle_e_key(key, app);
+            handle_e_key(key, app);
 
-            } else {
-                if app.can_input {
-                    app.input.push(c);
-                }
-            }
-            
-        },
-        _ => {}
+        } else if c == 'c' {
+
+            handle_c_key(app);
+
+        } else if c == 'r' {
+
+            handle_r_key(app);
+
+        } else if c == 'w' {
+
+            handle_w_key(app);
+
+        } else if c == 'l' || c == 'L' {
+
+            handle_l_key(c, app);
+
+        } else if c == 's' {
+
+            handle_s_key(app);
+
+        } else if c == '+' || c == '-' {
+
+            handle_priority_key(c, app);
+
+        } else if c == 't' {
+
+            handle_t_key(app);
+
+        } else if c == 'g' {
+
+            handle_g_key(app);
+
+        } else if c == 'x' || c == 'X' {
+
+            handle_x_key(c, app);
+
+        } else if app.can_input {
+            app.input.push(c);
+        }
     }
 }
 
@@ -859,27 +1153,10 @@ fn handle_e_key(key: KeyEvent, app: &mut App){
             }else if app.focus.tab_focus == 2 {
                 // events
 
-            }else if app.focus.tab_focus == 3 {
+            }else if kanban_has_focus(app) {
                 // kanban
-
-                if app.focus.chunk_focus[3] == 0 {
-                    // to do
-                    if app.kanban.projects[app.kanban.project_index].todo.len() > 0 {
-                        open_edit_popup(app, Popup::EditTodo, app.kanban.projects[app.kanban.project_index]
-                                                                .todo[app.kanban.todo_index].clone());
-                    }
-                }else if app.focus.chunk_focus[3] == 1 {
-                    // in progress
-                    if app.kanban.projects[app.kanban.project_index].in_progress.len() > 0 {
-                        open_edit_popup(app, Popup::EditInProgress, app.kanban.projects[app.kanban.project_index]
-                            .in_progress[app.kanban.in_progress_index].clone());
-                    }
-                }else if app.focus.chunk_focus[3] == 2 {
-                    // done
-                    if app.kanban.projects[app.kanban.project_index].done.len() > 0 {
-                        open_edit_popup(app, Popup::EditDone, app.kanban.projects[app.kanban.project_index]
-                            .done[app.kanban.done_index].clone());
-                    }
+                if let Some(card) = app.kanban.selected_card() {
+                    open_edit_popup(app, Popup::EditCard, card.edit_text());
                 }
             }
         }
@@ -904,65 +1181,415 @@ fn handle_n_key(key: KeyEvent, app: &mut App){
             }else if app.focus.tab_focus == 2 {
                 // events
 
-            }else if app.focus.tab_focus == 3 {
+            }else if kanban_has_focus(app) {
                 // kanban
+                open_popup(app, Popup::AddCard);
+            }
+        }
+    }
+}
 
-                if app.focus.chunk_focus[3] == 0 {
-                    // to do
-                    
-                    open_popup(app, Popup::AddTodo);
-                    
+fn handle_t_key(app: &mut App){
+    if app.can_input {
+        app.input.push('t');
+    }else if kanban_has_focus(app) && app.kanban.selected_card().is_some() {
+        // checklist of the selected task
+        app.kanban.checklist_index = 0;
+        open_checklist(app);
+    }
+}
 
-                }else if app.focus.chunk_focus[3] == 1 {
-                    // in progress
+fn handle_g_key(app: &mut App){
+    if app.can_input {
+        app.input.push('g');
+    }else if kanban_has_focus(app) {
+        // jump to a task by its number
+        open_popup(app, Popup::GoToCard);
+    }
+}
 
-                    open_popup(app, Popup::AddInProgress);
+fn handle_x_key(c: char, app: &mut App){
+    if app.can_input {
+        app.input.push(c);
+    }else if kanban_has_focus(app) && !app.kanban.projects.is_empty() {
+        // x exports the current project, X all of them, into the working directory
+        if c == 'x' {
+            open_edit_popup(app, Popup::ExportProject, export::file_name(app.kanban.project()));
+        } else {
+            open_edit_popup(app, Popup::ExportBoard, String::from(export::BOARD_EXPORT_FILE));
+        }
+    }
+}
 
-                }else if app.focus.chunk_focus[3] == 2 {
-                    // done
+// Writes the Markdown export to the file typed into the export popup
+fn export_markdown(app: &mut App) {
+    let path = app.input.trim().to_string();
 
-                    open_popup(app, Popup::AddDone);
-                }
+    if path.is_empty() {
+        return;
+    }
+
+    let markdown = match app.popup {
+        Popup::ExportProject => export::project_to_markdown(app.kanban.project()),
+        _ => export::board_to_markdown(&app.kanban.projects)
+    };
+
+    app.status = Some(match fs::write(&path, markdown) {
+        Ok(()) => format!("Exported to {}", path),
+        Err(e) => format!("Exporting to {} failed: {}", path, e)
+    });
+}
+
+// Keys while the checklist of the selected task is open
+fn handle_checklist_keys(app: &mut App, key: KeyEvent) {
+    let item_count = app.kanban.selected_card().map(|card| card.checklist.len()).unwrap_or(0);
+
+    match key.code {
+        KeyCode::Up => {
+            app.kanban.checklist_index = app.kanban.checklist_index.saturating_sub(1);
+        },
+
+        KeyCode::Down if app.kanban.checklist_index + 1 < item_count => {
+            app.kanban.checklist_index += 1;
+        },
+
+        KeyCode::Char(' ') => toggle_checklist_item(app),
+
+        KeyCode::Char('n') => open_popup(app, Popup::AddChecklistItem),
+
+        KeyCode::Char('e') => {
+            let index = app.kanban.checklist_index;
+            let name = app.kanban.selected_card()
+                .and_then(|card| card.checklist.get(index))
+                .map(|item| item.step_name.clone());
+
+            if let Some(name) = name {
+                open_edit_popup(app, Popup::EditChecklistItem, name);
+            }
+        },
+
+        _ => ()
+    }
+}
+
+fn toggle_checklist_item(app: &mut App) {
+    let index = app.kanban.checklist_index;
+
+    if app.kanban.selected_card_mut().map(|card| card.toggle_checklist_item(index)).unwrap_or(false) {
+        serialize_kanban(app);
+    }
+}
+
+fn clamp_checklist_index(app: &mut App) {
+    let item_count = app.kanban.selected_card().map(|card| card.checklist.len()).unwrap_or(0);
+
+    if app.kanban.checklist_index >= item_count {
+        app.kanban.checklist_index = item_count.saturating_sub(1);
+    }
+}
+
+fn handle_c_key(app: &mut App){
+    if app.can_input {
+        app.input.push('c');
+    }else if kanban_has_focus(app) {
+        // new kanban column
+        open_popup(app, Popup::AddColumn);
+    }
+}
+
+fn handle_r_key(app: &mut App){
+    if app.can_input {
+        app.input.push('r');
+    }else if kanban_has_focus(app) {
+        // rename the focused kanban column
+        open_edit_popup(app, Popup::RenameColumn, app.kanban.column().name.clone());
+    }
+}
+
+fn handle_w_key(app: &mut App){
+    if app.can_input {
+        app.input.push('w');
+    }else if kanban_has_focus(app) {
+        // set the wip limit of the focused kanban column
+        let limit = app.kanban.column().wip_limit.map(|limit| limit.to_string()).unwrap_or_default();
+        open_edit_popup(app, Popup::SetWipLimit, limit);
+    }
+}
+
+fn handle_l_key(c: char, app: &mut App){
+    if app.can_input {
+        app.input.push(c);
+    }else if kanban_has_focus(app) {
+        // l adds a label to the selected task, L removes one
+        if let Some(card) = app.kanban.selected_card() {
+            if c == 'l' {
+                open_popup(app, Popup::AddLabel);
+            } else {
+                let last_label = card.labels.last().cloned().unwrap_or_default();
+                open_edit_popup(app, Popup::RemoveLabel, last_label);
             }
         }
     }
 }
 
-fn deserialize_kanban(app: &mut App) -> seresult<()>{
+fn handle_s_key(app: &mut App){
+    if app.can_input {
+        app.input.push('s');
+    }else if kanban_has_focus(app) {
+        // toggle sorting the focused column by priority
+        let id = app.kanban.selected_card().map(|card| card.id.clone());
+        let column = app.kanban.column_mut();
 
-    let p= std::env::current_exe().unwrap();
-    let mut json_path = String::from(p.parent().unwrap().to_str().unwrap());
-    json_path.push_str("\\kanban.json");
+        column.sort_by_priority = !column.sort_by_priority;
+        column.sort();
 
-    let data = match fs::read_to_string(&json_path){
-        Ok(s) => s,
-        Err(_) => {
-            fs::File::create(&json_path).unwrap();
-            fs::read_to_string(&json_path).unwrap()
+        if let Some(id) = id {
+            app.kanban.select_card(&id);
         }
+
+        serialize_kanban(app);
+    }
+}
+
+fn handle_priority_key(c: char, app: &mut App){
+    if app.can_input {
+        app.input.push(c);
+    }else if kanban_has_focus(app) && app.kanban.change_priority(c == '+') {
+
+        serialize_kanban(app);
+    }
+}
+
+// Moves the selected task, asking for confirmation when the target column is at its WIP limit
+// or when an unfinished checklist would end up in the done column
+fn request_card_move(app: &mut App, to_column: usize) {
+    let unchecked = match app.kanban.selected_card() {
+        Some(card) => card.unchecked_count(),
+        None => return
+    };
+
+    let column = match app.kanban.project().columns.get(to_column) {
+        Some(column) => column,
+        None => return
     };
 
-    let project: Vec<KanbanProject> = match serde_json::from_str(&data){ 
-        Ok(s) => s,
-        Err(_) => Vec::new()
-    } ;
+    let mut warnings = Vec::new();
 
-    for p in project {
-        app.kanban.add_project(p);
+    if column.is_full() {
+        warnings.push(format!("{} is at its WIP limit ({}/{})",
+            column.name, column.cards.len(), column.wip_limit.unwrap_or_default()));
     }
 
-    Ok(())
+    if unchecked > 0 && app.kanban.project().is_done_column(to_column) {
+        warnings.push(format!("{} checklist item(s) not done", unchecked));
+    }
+
+    if !warnings.is_empty() {
+        app.kanban.pending_move = Some(to_column);
+        open_confirm_popup(app, Popup::ConfirmMove, warnings.join(", "));
+    } else if app.kanban.move_card(to_column) {
+        serialize_kanban(app);
+    }
+}
+
+fn deserialize_kanban(app: &mut App) {
+
+    match app.storage.load() {
+        Ok(board) => {
+            // never write over a file from a newer version
+            if board.is_newer() {
+                app.read_only = true;
+                app.status = Some(format!(
+                    "{} was written by a newer version of kanban (schema {}), it is opened read-only",
+                    app.board_path.display(), board.schema_version
+                ));
+            }
+
+            for p in board.projects {
+                app.kanban.add_project(p);
+            }
+
+            app.kanban.labels = board.labels;
+            app.daily_task.replace_tasks(board.daily_tasks);
+        },
+        Err(e) => {
+            // the file stays untouched until the user picked a way out
+            app.read_only = true;
+            app.recovery = Some(Recovery {
+                error: e.message(),
+                location: e.location(),
+                note: None
+            });
+            app.popup = Popup::Recovery;
+        }
+    }
+
+    app.stamp = app.storage.stamp();
+}
+
+fn check_board_changed(app: &mut App) {
+
+    if app.conflict || app.storage.stamp() == app.stamp {
+        return;
+    }
+
+    // an open popup or editor acts on the board it was opened on, the reload
+    // waits until it is closed. Saving from it before then is a conflict
+    if !matches!(app.popup, Popup::Disabled) || app.editor.is_some() {
+        return;
+    }
+
+    if app.unsaved {
+        app.conflict = true;
+    } else {
+        reload_board(app);
+    }
+}
+
+// Reads the board again, the selection stays on the same card
+fn reload_board(app: &mut App) -> bool {
+
+    match app.storage.load() {
+        Ok(board) => {
+            app.read_only = board.is_newer() || app.locked_out;
+            app.status = if board.is_newer() {
+                Some(format!(
+                    "{} was written by a newer version of kanban (schema {}), it is opened read-only",
+                    app.board_path.display(), board.schema_version
+                ))
+            } else {
+                None
+            };
+
+            app.kanban.replace_projects(board.projects);
+            app.kanban.labels = board.labels;
+            app.daily_task.replace_tasks(board.daily_tasks);
+            clamp_checklist_index(app);
+
+            if let Some(auto_commit) = &mut app.auto_commit {
+                auto_commit.reloaded(&app.kanban.projects, &app.daily_task.tasks);
+            }
+
+            app.stamp = app.storage.stamp();
+            app.unsaved = false;
+            app.conflict = false;
+            true
+        },
+        Err(e) => {
+            // tried again once the file changes, e.g. an editor saved it halfway
+            app.status = Some(format!("{} changed on disk but could not be read: {}", app.board_path.display(), e.message()));
+            false
+        }
+    }
+}
+
+fn lock_board(app: &mut App) {
+
+    match lock::acquire(&app.board_path) {
+        Ok(Lock::Acquired(lock)) => app.lock = Some(lock),
+        Ok(Lock::HeldBy(owner)) => {
+            app.locked_out = true;
+            app.read_only = true;
+
+            // the recovery screen comes first
+            if let Popup::Disabled = app.popup {
+                let owner = owner.map(|pid| format!(" (pid {})", pid)).unwrap_or_default();
+                open_confirm_popup(app, Popup::Locked, format!("Another instance{} has it open. r: read-only, t: take over", owner));
+            }
+        },
+        // still usable, just without the protection
+        Err(e) => app.status = Some(format!("Locking {} failed: {}", app.board_path.display(), e))
+    }
+}
+
+// The instance holding the lock gives it up when another one takes over
+fn check_lock(app: &mut App) {
+
+    if app.lock.as_ref().is_some_and(|lock| lock.taken_over()) {
+        app.lock = None;
+        app.locked_out = true;
+        app.read_only = true;
+        app.status = Some(String::from("Another instance took the board over, it is read-only here now"));
+    }
+}
+
+fn handle_locked_key(app: &mut App, key: KeyEvent) {
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('r') => close_popup(app),
+        KeyCode::Char('t') => {
+            match lock::take_over(&app.board_path) {
+                Ok(Lock::Acquired(lock)) => {
+                    app.lock = Some(lock);
+                    app.locked_out = false;
+
+                    // the other instance may have saved right before letting go
+                    reload_board(app);
+                },
+                Ok(Lock::HeldBy(_)) => app.status = Some(String::from("The other instance did not hand the board over")),
+                Err(e) => app.status = Some(format!("Taking over {} failed: {}", app.board_path.display(), e))
+            }
+
+            close_popup(app);
+        },
+        _ => ()
+    }
+}
+
+fn handle_conflict_key(app: &mut App, key: KeyEvent) {
+
+    match key.code {
+        KeyCode::Char('r') if reload_board(app) => close_popup(app),
+        KeyCode::Char('o') => {
+            // the version on disk is kept in the backups
+            app.conflict = false;
+            app.stamp = app.storage.stamp();
+            close_popup(app);
+            serialize_kanban(app);
+        },
+        _ => ()
+    }
 }
 
 fn serialize_kanban(app: &mut App) {
 
-    let p= std::env::current_exe().unwrap();
-    let mut json_path = String::from(p.parent().unwrap().to_str().unwrap());
-    json_path.push_str("\\kanban.json");
+    if app.read_only {
+        return;
+    }
+
+    // someone else wrote the board since it was read, the user picks which version stays
+    if app.conflict || app.storage.stamp() != app.stamp {
+        app.unsaved = true;
+        app.conflict = true;
+        return;
+    }
 
-    let j = serde_json::to_string_pretty(&app.kanban.projects).unwrap();
+    match app.storage.save(&app.kanban.projects, &app.daily_task.tasks, &app.kanban.labels) {
+        Ok(()) => {
+            app.status = None;
+            app.unsaved = false;
+            app.stamp = app.storage.stamp();
 
-    fs::write(json_path, j).unwrap();
+            if let Some(auto_commit) = &mut app.auto_commit {
+                auto_commit.saved(&app.kanban.projects, &app.daily_task.tasks);
+                commit_board(app);
+            }
+        },
+        Err(e) => {
+            app.status = Some(format!("Saving {} failed: {}", app.board_path.display(), e));
+            app.unsaved = true;
+        }
+    }
+}
+
+// Commits the saved changes once the --git-commit delay is over
+fn commit_board(app: &mut App) {
+
+    if let Some(auto_commit) = &mut app.auto_commit {
+        if let Err(e) = auto_commit.commit(false) {
+            app.status = Some(format!("Committing {} failed: {}", app.board_path.display(), e));
+        }
+    }
 }
 
 fn open_popup(app: &mut App, popup: Popup) {
@@ -975,6 +1602,7 @@ fn close_popup(app: &mut App) {
     app.input = "".to_string();
     app.popup = Popup::Disabled;
     app.can_input = false;
+    app.kanban.pending_move = None;
 }
 
 fn show_popup<B: Backend>(f: &mut Frame<B>, app: &mut App, title: &str, color: Color) {
@@ -1018,83 +1646,447 @@ fn show_popup<B: Backend>(f: &mut Frame<B>, app: &mut App, title: &str, color: C
         f.render_widget(input_area, margin_layout2[0]);
 }
 
-fn open_edit_popup(app: &mut App, popup: Popup, to_edit: String){
-    app.input = to_edit;
-    app.popup = popup;
-    app.can_input = true;
+fn open_checklist(app: &mut App) {
+    app.input = "".to_string();
+    app.popup = Popup::Checklist;
+    app.can_input = false;
+    clamp_checklist_index(app);
 }
 
-fn open_delete_popup(app: &mut App, popup: Popup, to_del: String){
-    app.input = to_del;
-    app.popup = popup;
+// Board focus for key commands, no popup may be open
+fn kanban_has_focus(app: &App) -> bool {
+    matches!(app.popup, Popup::Disabled) && app.focus.tab_focus == 3 && !app.kanban.projects.is_empty()
 }
 
-fn adjust_kanban_indexes_upon_project_change(app: &mut App){
-    app.focus.chunk_focus[3] = 0;
-    app.kanban.todo_index = 0;
-    app.kanban.in_progress_index = 1000;
-    app.kanban.done_index = 1000;
+fn show_checklist<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let card = match app.kanban.selected_card() {
+        Some(card) => card,
+        None => return
+    };
+
+    let items: Vec<ListItem> = card.checklist.iter()
+        .map(|item| ListItem::new(format!("{} {}", if item.done {"[x]"} else {"[ ]"}, item.step_name)))
+        .collect();
+
+    let title = match card.checklist_progress() {
+        Some((checked, total)) => format!("  {} {}/{}  ", card.title, checked, total),
+        None => format!("  {}  ", card.title)
+    };
+
+    let checklist = List::new(items)
+                        .block(Block::default()
+                            .title(title)
+                            .title_alignment(Alignment::Center)
+                            .borders(Borders::ALL)
+                            .border_type(BorderType::Rounded)
+                        )
+                        .style(Style::default().bg(Color::Black).fg(Color::White))
+                        .highlight_style(Style::default().bg(Color::Green).fg(Color::Black))
+                        .highlight_symbol(" ❱ ");
+
+    let hint = Paragraph::new(Span::from("n add  e edit  space toggle  delete remove  esc close"))
+                        .alignment(Alignment::Center)
+                        .style(Style::default().bg(Color::Black).fg(Color::DarkGray));
+
+    let popup_area = centered_rect(60, 60, f.size());
+
+    let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(
+                        [
+                            Constraint::Min(3),
+                            Constraint::Length(1)
+                        ].as_ref()
+                    ).split(popup_area);
+
+    app.kanban.checklist_state.select(if card.checklist.is_empty() {None} else {Some(app.kanban.checklist_index)});
+
+    f.render_widget(Clear, popup_area);
+    f.render_stateful_widget(checklist, chunks[0], &mut app.kanban.checklist_state);
+    f.render_widget(hint, chunks[1]);
 }
 
-fn delete_in_progress(app: &mut App){
-    if app.kanban.projects[app.kanban.project_index].in_progress.len() > 0{
-        //remove last element
-        if app.kanban.in_progress_index == app.kanban.projects[app.kanban.project_index].in_progress.len() - 1 {
+fn show_card_detail<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let card = match app.kanban.selected_card() {
+        Some(card) => card,
+        None => return
+    };
 
-            if app.kanban.in_progress_index == app.kanban.projects[app.kanban.project_index].in_progress.len() - 1 {
+    let project = app.kanban.project();
+    let done = project.is_done_column(app.kanban.column_index);
+    let now = Local::now();
+    let today = now.date_naive();
+    let label_style = Style::default().fg(Color::DarkGray);
+
+    let mut lines = vec![
+        Spans::from(vec![
+            Span::styled("Id           ", label_style),
+            Span::raw(format!("#{}  {}", card.number, card.id))
+        ]),
+        Spans::from(vec![
+            Span::styled("Column       ", label_style),
+            Span::raw(format!("{}, for {}", app.kanban.column().name, format_duration(now - card.entered_column_at())))
+        ]),
+        Spans::from(vec![
+            Span::styled("Priority     ", label_style),
+            Span::styled(format!("{}{}", card.priority.glyph(), card.priority.name()), Style::default().fg(card.priority.color()))
+        ])
+    ];
+
+    if !card.labels.is_empty() {
+        let mut spans = vec![Span::styled("Labels       ", label_style)];
+
+        for label in &card.labels {
+            spans.push(Span::styled(format!(" {} ", label), Style::default().bg(app.kanban.label_color(label)).fg(Color::Black)));
+            spans.push(Span::raw(" "));
+        }
 
-                app.kanban.projects[app.kanban.project_index].in_progress.remove(app.kanban.in_progress_index);
+        lines.push(Spans::from(spans));
+    }
 
-                if app.kanban.projects[app.kanban.project_index].in_progress.len() != 0 {
-                    app.kanban.in_progress_index = app.kanban.projects[app.kanban.project_index].in_progress.len() - 1; 
-                } else {
-                    app.kanban.in_progress_index = 0;
-                }
+    if let Some(due) = card.due {
+        lines.push(Spans::from(vec![
+            Span::styled("Due          ", label_style),
+            Span::styled(due.format("%d-%m-%Y").to_string(), if !done && card.is_overdue(today) {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else if !done && card.is_due_today(today) {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            })
+        ]));
+    }
 
-            }
-        } else {
-            app.kanban.projects[app.kanban.project_index].in_progress.remove(app.kanban.in_progress_index);
+    lines.push(Spans::from(vec![
+        Span::styled("Created      ", label_style),
+        Span::raw(card.created_at.format("%d-%m-%Y %H:%M").to_string())
+    ]));
+    lines.push(Spans::from(vec![
+        Span::styled("Updated      ", label_style),
+        Span::raw(card.updated_at.format("%d-%m-%Y %H:%M").to_string())
+    ]));
+
+    if let Some(lead_time) = card.lead_time(done) {
+        lines.push(Spans::from(vec![
+            Span::styled("Lead time    ", label_style),
+            Span::raw(format_duration(lead_time))
+        ]));
+    }
+
+    if let Some(cycle_time) = card.cycle_time(done) {
+        lines.push(Spans::from(vec![
+            Span::styled("Cycle time   ", label_style),
+            Span::raw(format_duration(cycle_time))
+        ]));
+    }
+
+    // * Description
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled("Description", label_style)));
+
+    if card.description.is_empty() {
+        lines.push(Spans::from(Span::styled("  press d to write one", label_style)));
+    }
+
+    for line in card.description.lines() {
+        lines.push(Spans::from(format!("  {}", line)));
+    }
+
+    // * Checklist
+    if let Some((checked, total)) = card.checklist_progress() {
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(Span::styled(format!("Checklist {}/{}", checked, total), label_style)));
+
+        for item in &card.checklist {
+            lines.push(Spans::from(format!("  {} {}", if item.done {"[x]"} else {"[ ]"}, item.step_name)));
         }
     }
+
+    // * History
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled("History", label_style)));
+
+    if card.transitions.is_empty() {
+        lines.push(Spans::from("  not moved yet"));
+    }
+
+    for transition in card.transitions.iter().rev() {
+        lines.push(Spans::from(format!("  {}  {} → {}",
+            transition.at.format("%d-%m-%Y %H:%M"), transition.from, transition.to)));
+    }
+
+    let detail = Paragraph::new(lines)
+                    .block(Block::default()
+                        .title(format!("  #{} {}  ", card.number, card.title))
+                        .title_alignment(Alignment::Center)
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                    )
+                    .style(Style::default().bg(Color::Black).fg(Color::White))
+                    .wrap(Wrap { trim: false })
+                    .scroll((app.kanban.detail_scroll, 0));
+
+    let hint = Paragraph::new(Span::from("d edit description  t checklist  ↑↓ scroll  esc close"))
+                    .alignment(Alignment::Center)
+                    .style(Style::default().bg(Color::Black).fg(Color::DarkGray));
+
+    let popup_area = centered_rect(80, 80, f.size());
+
+    let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(
+                        [
+                            Constraint::Min(3),
+                            Constraint::Length(1)
+                        ].as_ref()
+                    ).split(popup_area);
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(detail, chunks[0]);
+    f.render_widget(hint, chunks[1]);
 }
 
-fn delete_done(app: &mut App){
-    if app.kanban.projects[app.kanban.project_index].done.len() > 0{
-        //remove last element
-        if app.kanban.done_index == app.kanban.projects[app.kanban.project_index].done.len() - 1 {
+fn show_description_editor<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let editor = match &app.editor {
+        Some(editor) => editor,
+        None => return
+    };
 
-            if app.kanban.done_index == app.kanban.projects[app.kanban.project_index].done.len() - 1 {
+    let popup_area = centered_rect(80, 80, f.size());
+    let block = Block::default()
+                    .title("  Description  (Ctrl + s save, Esc cancel)  ")
+                    .title_alignment(Alignment::Center)
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .style(Style::default().bg(Color::Black).fg(Color::White));
 
-                app.kanban.projects[app.kanban.project_index].done.remove(app.kanban.done_index);
+    let inner = block.inner(popup_area);
 
-                if app.kanban.projects[app.kanban.project_index].done.len() != 0 {
-                    app.kanban.done_index = app.kanban.projects[app.kanban.project_index].done.len() - 1; 
-                } else {
-                    app.kanban.done_index = 0;
-                }
+    // keep the cursor inside the visible area
+    let scroll_y = (editor.row as u16).saturating_sub(inner.height.saturating_sub(1));
+    let scroll_x = (editor.col as u16).saturating_sub(inner.width.saturating_sub(1));
+
+    let text: Vec<Spans> = editor.lines.iter().map(|line| Spans::from(&line[..])).collect();
+    let text = Paragraph::new(text)
+                    .block(block)
+                    .scroll((scroll_y, scroll_x));
 
+    f.render_widget(Clear, popup_area);
+    f.render_widget(text, popup_area);
+    f.set_cursor(inner.x + editor.col as u16 - scroll_x, inner.y + editor.row as u16 - scroll_y);
+}
+
+// Every key goes to the description editor while it is open
+fn handle_editor_key(app: &mut App, key: KeyEvent) {
+    let editor = match app.editor.as_mut() {
+        Some(editor) => editor,
+        None => return
+    };
+
+    match key.code {
+        KeyCode::Esc => {
+            // discard the changes
+            app.editor = None;
+            app.popup = Popup::CardDetail;
+        },
+
+        KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => {
+            let description = editor.text();
+
+            if let Some(card) = app.kanban.selected_card_mut() {
+                card.set_description(description.trim_end());
             }
-        } else {
-            app.kanban.projects[app.kanban.project_index].done.remove(app.kanban.done_index);
+
+            app.editor = None;
+            app.popup = Popup::CardDetail;
+            serialize_kanban(app);
+        },
+
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => editor.insert_char(c),
+        KeyCode::Tab => {
+            for _ in 0..4 {
+                editor.insert_char(' ');
+            }
+        },
+        KeyCode::Enter => editor.new_line(),
+        KeyCode::Backspace => editor.delete_back(),
+        KeyCode::Delete => editor.delete_forward(),
+        KeyCode::Left => editor.move_left(),
+        KeyCode::Right => editor.move_right(),
+        KeyCode::Up => editor.move_up(),
+        KeyCode::Down => editor.move_down(),
+        KeyCode::Home => editor.move_home(),
+        KeyCode::End => editor.move_end(),
+        _ => ()
+    }
+}
+
+// Keys while the details of the selected task are shown
+fn handle_detail_keys(app: &mut App, key: KeyEvent) {
+
+    match key.code {
+        KeyCode::Up => {
+            app.kanban.detail_scroll = app.kanban.detail_scroll.saturating_sub(1);
+        },
+
+        KeyCode::Down => {
+            app.kanban.detail_scroll = app.kanban.detail_scroll.saturating_add(1);
+        },
+
+        KeyCode::Char('d') => {
+            let description = app.kanban.selected_card().map(|card| card.description.clone()).unwrap_or_default();
+
+            app.editor = Some(TextEditor::new(&description));
+            app.popup = Popup::EditDescription;
+        },
+
+        KeyCode::Char('t') => {
+            app.kanban.checklist_index = 0;
+            open_checklist(app);
+        },
+
+        _ => ()
+    }
+}
+
+fn show_recovery<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let recovery = match &app.recovery {
+        Some(recovery) => recovery,
+        None => return
+    };
+
+    let label_style = Style::default().fg(Color::DarkGray);
+
+    let mut lines = vec![
+        Spans::from(Span::styled("The board file could not be read, it was not changed",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))),
+        Spans::from(""),
+        Spans::from(vec![Span::styled("File   ", label_style), Span::raw(app.board_path.display().to_string())]),
+        Spans::from(vec![Span::styled("Error  ", label_style), Span::raw(recovery.error.clone())])
+    ];
+
+    // the broken line with a marker under the column
+    if let Some((line, column)) = recovery.location {
+        let content = fs::read_to_string(&app.board_path).unwrap_or_default();
+
+        if let Some(text) = content.lines().nth(line.saturating_sub(1)) {
+            lines.push(Spans::from(""));
+            lines.push(Spans::from(vec![Span::styled(format!("{:>6} | ", line), label_style), Span::raw(text.to_string())]));
+            lines.push(Spans::from(vec![
+                Span::styled("       | ", label_style),
+                Span::styled(format!("{}^", " ".repeat(column.saturating_sub(1))), Style::default().fg(Color::Red))
+            ]));
         }
     }
+
+    lines.push(Spans::from(""));
+    lines.push(Spans::from("r    open read-only, nothing is saved"));
+
+    if has_backups(app) {
+        let backups = storage::list_backups(&app.board_path).map(|backups| backups.len()).unwrap_or(0);
+        lines.push(Spans::from(format!("b    load the newest readable backup ({} available)", backups)));
+    }
+
+    lines.push(Spans::from("f    start fresh, the broken file is renamed to *.broken-<date> first"));
+    lines.push(Spans::from("Esc  quit"));
+
+    if let Some(note) = &recovery.note {
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(Span::styled(note.clone(), Style::default().fg(Color::Yellow))));
+    }
+
+    let screen = Paragraph::new(lines)
+                    .block(Block::default()
+                        .title("  Recovery  ")
+                        .title_alignment(Alignment::Center)
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                    )
+                    .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, f.size());
+    f.render_widget(screen, f.size());
+}
+
+// Only JSON boards keep rotating backups
+fn has_backups(app: &App) -> bool {
+
+    storage::format_of(&app.board_path) == storage::Format::Json
 }
 
-fn delete_todo(app: &mut App){
-    if app.kanban.projects[app.kanban.project_index].todo.len() > 0{
+// Returns true when the app should quit
+fn handle_recovery_key(app: &mut App, key: KeyEvent) -> bool {
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => return true,
+
+        KeyCode::Char('r') => {
+            app.recovery = None;
+            app.popup = Popup::Disabled;
+        },
+
+        KeyCode::Char('b') if has_backups(app) => {
+            match storage::load_newest_backup(&app.board_path, app.cipher.as_ref()) {
+                Ok(Some((backup, board))) => {
+                    for p in board.projects {
+                        app.kanban.add_project(p);
+                    }
 
-        //remove last element
-        if app.kanban.todo_index == app.kanban.projects[app.kanban.project_index].todo.len() - 1{
-            app.kanban.projects[app.kanban.project_index].todo.remove(app.kanban.todo_index);
+                    app.kanban.labels = board.labels;
+                    app.daily_task.replace_tasks(board.daily_tasks);
 
-            if app.kanban.projects[app.kanban.project_index].todo.len() != 0{
-                app.kanban.todo_index = app.kanban.projects[app.kanban.project_index].todo.len() - 1;
-            }else {
-                app.kanban.todo_index = 0;
+                    // the broken file is backed up by the next save
+                    app.read_only = app.locked_out;
+                    app.recovery = None;
+                    app.popup = Popup::Disabled;
+                    app.status = Some(format!("Loaded backup {}", backup.path.display()));
+                },
+                Ok(None) => set_recovery_note(app, String::from("There is no readable backup")),
+                Err(e) => set_recovery_note(app, format!("Reading the backups failed: {}", e))
             }
-            
-        }else {
-            app.kanban.projects[app.kanban.project_index].todo.remove(app.kanban.todo_index);
-        }
+        },
+
+        KeyCode::Char('f') => {
+            match storage::move_aside(&app.board_path) {
+                Ok(broken) => {
+                    // a new database is created on the first save
+                    app.storage = storage::open(&app.board_path, app.cipher.clone());
+                    app.stamp = app.storage.stamp();
+                    app.read_only = app.locked_out;
+                    app.recovery = None;
+                    app.popup = Popup::Disabled;
+                    app.status = Some(format!("Started fresh, the broken board is kept as {}", broken.display()));
+                },
+                Err(e) => set_recovery_note(app, format!("Moving the broken file failed: {}", e))
+            }
+        },
+
+        _ => ()
     }
-}
\ No newline at end of file
+
+    false
+}
+
+fn set_recovery_note(app: &mut App, note: String) {
+
+    if let Some(recovery) = app.recovery.as_mut() {
+        recovery.note = Some(note);
+    }
+}
+
+fn open_edit_popup(app: &mut App, popup: Popup, to_edit: String){
+    app.input = to_edit;
+    app.popup = popup;
+    app.can_input = true;
+}
+
+fn open_delete_popup(app: &mut App, popup: Popup, to_del: String){
+    app.input = to_del;
+    app.popup = popup;
+}
+
+fn open_confirm_popup(app: &mut App, popup: Popup, message: String){
+    app.input = message;
+    app.popup = popup;
+}
+
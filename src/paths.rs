@@ -0,0 +1,186 @@
+use std::{
+    env, fs, io,
+
+    path::{Path, PathBuf}
+};
+
+use crate::cli::Args;
+
+pub const BOARD_FILE_NAME: &str = "kanban.json";
+pub const BOARD_FILE_ENV: &str = "KANBAN_FILE";
+
+// Boards that belong to a repository, found like git finds .git
+pub const LOCAL_BOARD_FILE: &str = ".kanban.json";
+pub const LOCAL_BOARD_DIR: &str = ".kanban";
+
+// Where the board that is open came from, shown in the header
+pub enum BoardSource {
+    Argument,
+    Environment,
+    // directory the board was found in
+    Local(PathBuf),
+    Global
+}
+
+impl BoardSource {
+
+    pub fn label(&self, path: &Path) -> String {
+
+        match self {
+            BoardSource::Argument | BoardSource::Environment => path.display().to_string(),
+            BoardSource::Local(dir) => {
+                let name = dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| dir.display().to_string());
+                format!("{} board", name)
+            },
+            BoardSource::Global => String::from("global board")
+        }
+    }
+}
+
+// * Board file lookup
+// --file beats KANBAN_FILE, both beat a board of the repository the working
+// directory is in, which beats the global board in the platform data directory
+pub fn resolve_board_path(args: &Args) -> io::Result<(PathBuf, BoardSource)> {
+
+    if let Some(file) = &args.file {
+        return Ok((file.clone(), BoardSource::Argument));
+    }
+
+    if let Some(file) = env::var_os(BOARD_FILE_ENV).filter(|file| !file.is_empty()) {
+        return Ok((PathBuf::from(file), BoardSource::Environment));
+    }
+
+    if !args.global {
+        if let Some(board) = find_local_board(&env::current_dir()?) {
+            let dir = board.parent().map(Path::to_path_buf).unwrap_or_default();
+            return Ok((board, BoardSource::Local(dir)));
+        }
+    }
+
+    let path = default_board_path()?;
+    migrate_legacy_board(&path, &legacy_board_paths())?;
+
+    Ok((path, BoardSource::Global))
+}
+
+// .kanban.json or a .kanban directory in `start` or the closest parent that has one
+pub fn find_local_board(start: &Path) -> Option<PathBuf> {
+
+    for dir in start.ancestors() {
+        let file = dir.join(LOCAL_BOARD_FILE);
+
+        if file.is_file() {
+            return Some(file);
+        }
+
+        let board = dir.join(LOCAL_BOARD_DIR);
+
+        if board.is_dir() {
+            return Some(board);
+        }
+    }
+
+    None
+}
+
+// ~/.local/share/kanban/kanban.json on Linux, the matching data directory elsewhere
+pub fn default_board_path() -> io::Result<PathBuf> {
+
+    match dirs::data_dir() {
+        Some(dir) => Ok(dir.join("kanban").join(BOARD_FILE_NAME)),
+        None => Err(io::Error::new(io::ErrorKind::NotFound,
+            format!("no data directory found, use --file or {}", BOARD_FILE_ENV)))
+    }
+}
+
+// Older versions kept the board next to the executable and built the path with a
+// hard coded "\\", which outside of Windows names a file "release\kanban.json" one level up
+fn legacy_board_paths() -> Vec<PathBuf> {
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(_) => return Vec::new()
+    };
+
+    match exe.parent() {
+        Some(dir) => vec![
+            dir.join(BOARD_FILE_NAME),
+            PathBuf::from(format!("{}\\{}", dir.display(), BOARD_FILE_NAME))
+        ],
+        None => Vec::new()
+    }
+}
+
+// Moves a board from the first legacy location that has one to the new one,
+// unless there is a board already
+fn migrate_legacy_board(path: &Path, legacy_paths: &[PathBuf]) -> io::Result<()> {
+
+    if path.exists() {
+        return Ok(());
+    }
+
+    let legacy = match legacy_paths.iter().find(|legacy| legacy.is_file()) {
+        Some(legacy) => legacy,
+        None => return Ok(())
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // rename fails across file systems, copy and remove instead
+    if fs::rename(legacy, path).is_err() {
+        fs::copy(legacy, path)?;
+        fs::remove_file(legacy)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("kanban-paths-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn the_closest_local_board_is_found() {
+        let root = temp_dir("local");
+        let nested = root.join("src").join("storage");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(LOCAL_BOARD_FILE), "").unwrap();
+
+        assert_eq!(find_local_board(&nested), Some(root.join(LOCAL_BOARD_FILE)));
+
+        fs::create_dir(root.join("src").join(LOCAL_BOARD_DIR)).unwrap();
+        assert_eq!(find_local_board(&nested), Some(root.join("src").join(LOCAL_BOARD_DIR)));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_legacy_board_moves_unless_there_is_one_already() {
+        let dir = temp_dir("legacy");
+        let path = dir.join("data").join(BOARD_FILE_NAME);
+        let legacy = [dir.join("missing.json"), dir.join(BOARD_FILE_NAME)];
+        fs::write(&legacy[1], "[]").unwrap();
+
+        migrate_legacy_board(&path, &legacy).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        assert!(!legacy[1].exists());
+
+        fs::write(&legacy[1], "[{}]").unwrap();
+        migrate_legacy_board(&path, &legacy).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        assert!(legacy[1].exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
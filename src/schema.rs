@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::data::{KanbanProject, Labels, Task};
+
+// * On-disk format of the board
+// Bump this and append a migration whenever the persisted data model changes
+pub const SCHEMA_VERSION: u32 = 2;
+
+// MIGRATIONS[n] upgrades a version n document to version n + 1
+const MIGRATIONS: [fn(Value) -> Result<Value, String>; SCHEMA_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    pub schema_version: u32,
+    pub projects: Vec<KanbanProject>,
+    pub daily_tasks: Vec<Task>,
+    #[serde(default)]
+    pub labels: Labels
+}
+
+#[derive(Serialize)]
+struct DocumentRef<'a> {
+    schema_version: u32,
+    projects: &'a [KanbanProject],
+    daily_tasks: &'a [Task],
+    labels: &'a Labels
+}
+
+pub fn to_json(projects: &[KanbanProject], daily_tasks: &[Task], labels: &Labels) -> serde_json::Result<String> {
+
+    serde_json::to_string_pretty(&DocumentRef {
+        schema_version: SCHEMA_VERSION,
+        projects,
+        daily_tasks,
+        labels
+    })
+}
+
+// Files written before the envelope existed are a bare array of projects
+pub fn version_of(document: &Value) -> Result<u32, String> {
+
+    match document {
+        Value::Array(_) => Ok(0),
+        Value::Object(map) => match map.get("schema_version") {
+            Some(version) => version.as_u64()
+                                .map(|v| v as u32)
+                                .ok_or_else(|| format!("schema_version is not a number: {}", version)),
+            None => Err(String::from("schema_version is missing"))
+        },
+        _ => Err(String::from("the board is neither a list of projects nor an object"))
+    }
+}
+
+// Runs every migration from the version of the document up to the current one
+pub fn upgrade(mut document: Value) -> Result<Value, String> {
+    let version = version_of(&document)?;
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        document = migration(document)
+                    .map_err(|e| format!("upgrading from schema {} failed: {}", from, e))?;
+    }
+
+    Ok(document)
+}
+
+// * Migrations
+fn migrate_v0_to_v1(document: Value) -> Result<Value, String> {
+
+    match document {
+        Value::Array(projects) => Ok(json!({
+            "schema_version": 1,
+            "projects": projects
+        })),
+        _ => Err(String::from("expected a list of projects"))
+    }
+}
+
+// Daily tasks are kept next to the projects
+fn migrate_v1_to_v2(mut document: Value) -> Result<Value, String> {
+    let map = document.as_object_mut().ok_or_else(|| String::from("expected an object"))?;
+
+    map.entry("daily_tasks").or_insert_with(|| json!([]));
+    map.insert(String::from("schema_version"), json!(2));
+
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_project_lists_upgrade_to_the_current_schema() {
+        let document = json!([
+            { "name": "Website", "todo": ["Fix the header"], "in_progress": [], "done": ["Set up the repository"] }
+        ]);
+
+        let upgraded = upgrade(document).unwrap();
+        assert_eq!(version_of(&upgraded), Ok(SCHEMA_VERSION));
+
+        let document: Document = serde_json::from_value(upgraded).unwrap();
+        let project = &document.projects[0];
+
+        assert_eq!(project.columns.len(), 3);
+        assert_eq!(project.columns[0].cards[0].title, "Fix the header");
+        assert_eq!(project.columns[2].cards[0].number, 2);
+        assert!(document.daily_tasks.is_empty() && document.labels.is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs,
+
+    path::{Path, PathBuf}
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::App,
+    storage
+};
+
+// * UI state
+// Where the user left off, kept in kanban.json.state.json next to the board.
+// Projects and cards are remembered by id, the indexes are only the fallback
+// when they were deleted or moved by someone else in the meantime
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct UiState {
+    pub tab: i32,
+    pub chunk_focus: Vec<i32>,
+    pub project_id: Option<String>,
+    pub project_index: usize,
+    pub column_index: usize,
+    pub card_id: Option<String>,
+    pub card_index: usize
+}
+
+pub fn state_path(board: &Path) -> PathBuf {
+
+    storage::sibling_path(board, ".state.json")
+}
+
+impl UiState {
+
+    pub fn capture(app: &App) -> UiState {
+        let kanban = &app.kanban;
+        let project = kanban.projects.get(kanban.project_index);
+
+        UiState {
+            tab: app.focus.tab_focus,
+            chunk_focus: app.focus.chunk_focus.clone(),
+            project_id: project.map(|project| project.id.clone()),
+            project_index: kanban.project_index,
+            column_index: kanban.column_index,
+            card_id: project.and_then(|_| kanban.selected_card()).map(|card| card.id.clone()),
+            card_index: kanban.card_index
+        }
+    }
+
+    // Anything that no longer fits the board is clamped
+    pub fn restore(&self, app: &mut App) {
+
+        if app.tab.contains_key(&self.tab) {
+            app.focus.tab_focus = self.tab;
+        }
+
+        for (i, focus) in app.focus.chunk_focus.iter_mut().enumerate() {
+            let max = app.chunk_size.get(i).copied().unwrap_or(0);
+
+            *focus = self.chunk_focus.get(i).copied().unwrap_or(0).clamp(0, max);
+        }
+
+        let kanban = &mut app.kanban;
+
+        if kanban.projects.is_empty() {
+            return;
+        }
+
+        kanban.project_index = self.project_id.as_ref()
+                                .and_then(|id| kanban.projects.iter().position(|project| &project.id == id))
+                                .unwrap_or_else(|| self.project_index.min(kanban.projects.len() - 1));
+
+        if !self.card_id.as_ref().is_some_and(|id| kanban.select_card(id)) {
+            kanban.column_index = self.column_index;
+            kanban.card_index = self.card_index;
+            kanban.clamp_selection();
+        }
+    }
+}
+
+// A missing or unreadable state file starts from the top, it only holds the selection
+pub fn load(board: &Path) -> Option<UiState> {
+    let content = fs::read_to_string(state_path(board)).ok()?;
+
+    serde_json::from_str(&content).ok()
+}
+
+pub fn save(board: &Path, state: &UiState) {
+
+    if let Ok(content) = serde_json::to_string_pretty(state) {
+        let _ = storage::write_atomic(&state_path(board), content.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Card, KanbanProject};
+
+    fn app() -> App {
+        let mut app = App::default();
+        let mut project = KanbanProject::new(String::from("Website"));
+        project.columns[1].cards.push(Card::new("Fix the header"));
+        app.kanban.add_project(project);
+
+        app
+    }
+
+    #[test]
+    fn a_selection_that_no_longer_fits_is_clamped() {
+        let mut app = app();
+
+        let state = UiState {
+            tab: 7,
+            chunk_focus: vec![4, 4, 4, 4],
+            project_id: Some(String::from("deleted")),
+            project_index: 3,
+            column_index: 9,
+            card_id: Some(String::from("deleted")),
+            card_index: 9
+        };
+        state.restore(&mut app);
+
+        assert_eq!(app.focus.tab_focus, 3);
+        assert_eq!(app.focus.chunk_focus, [0, 1, 0, 0]);
+        assert_eq!((app.kanban.project_index, app.kanban.column_index, app.kanban.card_index), (0, 2, 0));
+    }
+
+    #[test]
+    fn a_card_is_found_by_its_id_after_it_moved() {
+        let mut app = app();
+        let card_id = app.kanban.projects[0].columns[1].cards[0].id.clone();
+
+        let state = UiState {
+            card_id: Some(card_id),
+            ..UiState::default()
+        };
+        state.restore(&mut app);
+
+        assert_eq!((app.kanban.column_index, app.kanban.card_index), (1, 0));
+    }
+}
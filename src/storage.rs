@@ -0,0 +1,680 @@
+use std::{
+    fs, io,
+
+    io::Write,
+
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime}
+};
+
+use chrono::prelude::*;
+
+use serde_json::Value;
+
+use crate::{
+    data::{KanbanProject, Labels, Task},
+    schema::{self, Document, SCHEMA_VERSION}
+};
+
+mod sqlite;
+use sqlite::SqliteStorage;
+
+mod markdown;
+use markdown::MarkdownStorage;
+
+mod rows;
+use rows::Rows;
+
+pub mod journal;
+use journal::COMPACT_AFTER;
+
+pub mod crypto;
+use crypto::Cipher;
+
+// Older versions of the board kept next to it, the oldest is removed first
+pub const BACKUP_COUNT: usize = 10;
+
+// Journaled saves keep the version before them at most this often, every
+// new snapshot keeps one
+const BACKUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+// * Backends
+pub trait Storage {
+    fn load(&mut self) -> Result<LoadedBoard, LoadError>;
+
+    fn save(&mut self, projects: &[KanbanProject], daily_tasks: &[Task], labels: &Labels) -> io::Result<()>;
+
+    fn stamp(&self) -> Stamp;
+}
+
+// Size and modification time of the files a board is kept in, it changes
+// whenever the board is written, by this or any other program
+#[derive(PartialEq, Clone, Default)]
+pub struct Stamp(Vec<(PathBuf, u64, Option<SystemTime>)>);
+
+impl Stamp {
+
+    // Missing files are left out
+    pub fn of(paths: Vec<PathBuf>) -> Stamp {
+        let mut files = Vec::new();
+
+        for path in paths {
+            if let Ok(metadata) = fs::metadata(&path) {
+                files.push((path, metadata.len(), metadata.modified().ok()));
+            }
+        }
+
+        Stamp(files)
+    }
+}
+
+#[derive(PartialEq)]
+pub enum Format {
+    Json,
+    Sqlite,
+    Markdown
+}
+
+// A directory, or a path ending in a separator, is a Markdown board. .db, .sqlite
+// and .sqlite3 files are SQLite databases, everything else is JSON
+pub fn format_of(path: &Path) -> Format {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+    if path.is_dir() || path.as_os_str().to_string_lossy().ends_with(std::path::is_separator) {
+        return Format::Markdown;
+    }
+
+    match extension.as_str() {
+        "db" | "sqlite" | "sqlite3" => Format::Sqlite,
+        _ => Format::Json
+    }
+}
+
+// Only JSON boards can be encrypted, the cipher is ignored for the others
+pub fn open(path: &Path, cipher: Option<Cipher>) -> Box<dyn Storage> {
+
+    match format_of(path) {
+        Format::Json => Box::new(JsonStorage::new(path, cipher)),
+        Format::Sqlite => Box::new(SqliteStorage::new(path)),
+        Format::Markdown => Box::new(MarkdownStorage::new(path))
+    }
+}
+
+// The whole board in one snapshot file. A save only appends what changed to
+// the journal next to it, the snapshot is rewritten once the journal is long
+pub struct JsonStorage {
+    path: PathBuf,
+    cipher: Option<Cipher>,
+    // the board as the snapshot and the journal have it, None when that is not
+    // known and the next save has to write a new snapshot
+    written: Option<Rows>,
+    // entries in the journal
+    journaled: usize,
+    // the last backup a journaled save took
+    backed_up: Option<Instant>,
+    // the files right after the last load or save
+    stamp: Stamp
+}
+
+impl JsonStorage {
+
+    pub fn new(path: &Path, cipher: Option<Cipher>) -> JsonStorage {
+
+        JsonStorage {
+            path: path.to_path_buf(),
+            cipher,
+            written: None,
+            journaled: 0,
+            backed_up: None,
+            stamp: Stamp::default()
+        }
+    }
+
+    // The snapshot with the journal replayed on top of it
+    fn read(&mut self) -> Result<LoadedBoard, LoadError> {
+        self.written = None;
+        self.stamp = self.stamp();
+
+        let mut board = load_board(&self.path, self.cipher.as_ref())?;
+        let journal = journal::read(&self.path, self.cipher.as_ref())?;
+
+        if !journal.entries.is_empty() {
+            let mut rows = Rows::from_board(&board.projects, &board.daily_tasks, &board.labels).map_err(LoadError::Parse)?;
+
+            for entry in &journal.entries {
+                journal::replay(&mut rows, entry);
+            }
+
+            let replayed = decode(rows.to_document(SCHEMA_VERSION)?, None)?;
+            board.projects = replayed.projects;
+            board.daily_tasks = replayed.daily_tasks;
+            board.labels = replayed.labels;
+        }
+
+        // a missing or older snapshot is written in full first, the journal is
+        // always on top of one in the current schema. A torn entry is dropped the same way
+        if self.path.is_file() && board.schema_version == SCHEMA_VERSION && !journal.torn {
+            self.written = Some(Rows::from_board(&board.projects, &board.daily_tasks, &board.labels).map_err(LoadError::Parse)?);
+        }
+
+        self.journaled = journal.entries.len();
+
+        Ok(board)
+    }
+
+    fn write_snapshot(&mut self, projects: &[KanbanProject], daily_tasks: &[Task], labels: &Labels, rows: Rows) -> io::Result<()> {
+        let content = schema::to_json(projects, daily_tasks, labels)?;
+
+        // the backup is a copy of the file, so it stays encrypted as well
+        match &self.cipher {
+            Some(cipher) => save_with_backup(&self.path, &cipher.encrypt(content.as_bytes()).map_err(io::Error::other)?)?,
+            None => save_with_backup(&self.path, content.as_bytes())?
+        }
+
+        // replaying the journal on the new snapshot changes nothing, a crash
+        // before it is removed loses nothing. One that could not be read is
+        // kept, its changes are not in the board
+        if self.written.is_none() && journal::read(&self.path, self.cipher.as_ref()).is_err() {
+            set_journal_aside(&self.path, &format!(".broken-{}", Local::now().format(BACKUP_TIME_FORMAT)))?;
+        } else {
+            journal::clear(&self.path)?;
+        }
+
+        self.written = Some(rows);
+        self.journaled = 0;
+
+        Ok(())
+    }
+
+    // Folds the journal into a new snapshot
+    pub fn compact(&mut self) -> io::Result<()> {
+        let board = self.read().map_err(|e| io::Error::other(e.message()))?;
+
+        if self.journaled == 0 {
+            return Ok(());
+        }
+
+        if board.is_newer() {
+            return Err(io::Error::other(format!(
+                "the board was written by a newer version (schema {}, this one writes {})",
+                board.schema_version, SCHEMA_VERSION
+            )));
+        }
+
+        let rows = Rows::from_board(&board.projects, &board.daily_tasks, &board.labels)?;
+
+        self.write_snapshot(&board.projects, &board.daily_tasks, &board.labels, rows)
+    }
+}
+
+impl Storage for JsonStorage {
+
+    fn load(&mut self) -> Result<LoadedBoard, LoadError> {
+
+        self.read()
+    }
+
+    fn save(&mut self, projects: &[KanbanProject], daily_tasks: &[Task], labels: &Labels) -> io::Result<()> {
+        let rows = Rows::from_board(projects, daily_tasks, labels)?;
+
+        // overwriting changes of another program, diff against what is really
+        // there or write a new snapshot when it can not be read
+        let stale = self.stamp() != self.stamp;
+
+        if stale {
+            let _ = self.read();
+        }
+
+        let entries = match &self.written {
+            Some(written) => Some(journal::entries(written, &rows)?),
+            None => None
+        };
+
+        match entries {
+            Some(entries) if self.journaled + entries.len() <= COMPACT_AFTER => {
+                if !entries.is_empty() {
+                    // the changes of another program are always kept before they are overwritten
+                    if stale || self.backed_up.is_none_or(|at| at.elapsed() >= BACKUP_INTERVAL) {
+                        backup(&self.path)?;
+                        self.backed_up = Some(Instant::now());
+                    }
+
+                    journal::append(&self.path, &entries, self.cipher.as_ref())?;
+
+                    self.journaled += entries.len();
+                    self.written = Some(rows);
+                }
+            },
+            _ => self.write_snapshot(projects, daily_tasks, labels, rows)?
+        }
+
+        self.stamp = self.stamp();
+
+        Ok(())
+    }
+
+    fn stamp(&self) -> Stamp {
+
+        Stamp::of(vec![self.path.clone(), journal::journal_path(&self.path)])
+    }
+}
+
+// * Loading
+pub enum LoadError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    Schema(String),
+    Database(rusqlite::Error),
+    Encryption(String)
+}
+
+impl LoadError {
+
+    pub fn message(&self) -> String {
+
+        match self {
+            LoadError::Io(e) => e.to_string(),
+            LoadError::Parse(e) => e.to_string(),
+            LoadError::Schema(e) => e.clone(),
+            LoadError::Database(e) => e.to_string(),
+            LoadError::Encryption(e) => e.clone()
+        }
+    }
+
+    // 1 based line and column of a parse error
+    pub fn location(&self) -> Option<(usize, usize)> {
+
+        match self {
+            // errors found after the migrations have no position in the file
+            LoadError::Parse(e) if e.line() > 0 => Some((e.line(), e.column())),
+            _ => None
+        }
+    }
+}
+
+pub struct LoadedBoard {
+    pub projects: Vec<KanbanProject>,
+    pub daily_tasks: Vec<Task>,
+    pub labels: Labels,
+    // schema the file was written with, before any migration
+    pub schema_version: u32
+}
+
+impl LoadedBoard {
+
+    // Written by a newer version of the app, saving would drop what it added
+    pub fn is_newer(&self) -> bool {
+
+        self.schema_version > SCHEMA_VERSION
+    }
+}
+
+// A missing or empty file is an empty board, it is created on the first save
+pub fn load_board(path: &Path, cipher: Option<&Cipher>) -> Result<LoadedBoard, LoadError> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(empty_board()),
+        Err(e) => return Err(LoadError::Io(e))
+    };
+
+    let content = match (crypto::is_encrypted(&content), cipher) {
+        (true, Some(cipher)) => cipher.decrypt(&content).map_err(LoadError::Encryption)?,
+        (true, None) => return Err(LoadError::Encryption(String::from("the board is encrypted"))),
+        (false, _) => content
+    };
+
+    let data = String::from_utf8(content)
+                .map_err(|e| LoadError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+
+    if data.trim().is_empty() {
+        return Ok(empty_board());
+    }
+
+    let value: Value = serde_json::from_str(&data).map_err(LoadError::Parse)?;
+
+    decode(value, Some(&data))
+}
+
+// Migrates a board document to the current schema, `text` is the file it was
+// parsed from, errors in it keep their line and column
+fn decode(value: Value, text: Option<&str>) -> Result<LoadedBoard, LoadError> {
+    let schema_version = schema::version_of(&value).map_err(LoadError::Schema)?;
+
+    let document: Document = if schema_version == SCHEMA_VERSION {
+        match text {
+            Some(text) => serde_json::from_str(text).map_err(LoadError::Parse)?,
+            None => serde_json::from_value(value).map_err(LoadError::Parse)?
+        }
+    } else if schema_version < SCHEMA_VERSION {
+        let upgraded = schema::upgrade(value).map_err(LoadError::Schema)?;
+        serde_json::from_value(upgraded).map_err(LoadError::Parse)?
+    } else {
+        // fields this version does not know about are skipped
+        serde_json::from_value(value).map_err(|e| LoadError::Schema(format!(
+            "the board was written by a newer version (schema {}, this one reads up to {}): {}",
+            schema_version, SCHEMA_VERSION, e
+        )))?
+    };
+
+    Ok(LoadedBoard {
+        projects: document.projects,
+        daily_tasks: document.daily_tasks,
+        labels: document.labels,
+        schema_version
+    })
+}
+
+fn empty_board() -> LoadedBoard {
+
+    LoadedBoard {
+        projects: Vec::new(),
+        daily_tasks: Vec::new(),
+        labels: Labels::new(),
+        schema_version: SCHEMA_VERSION
+    }
+}
+
+// Newest backup that can still be read and written back, with its journal replayed
+pub fn load_newest_backup(path: &Path, cipher: Option<&Cipher>) -> io::Result<Option<(Backup, LoadedBoard)>> {
+
+    for backup in list_backups(path)? {
+        match JsonStorage::new(&backup.path, cipher.cloned()).read() {
+            Ok(board) if !board.is_newer() => return Ok(Some((backup, board))),
+            _ => ()
+        }
+    }
+
+    Ok(None)
+}
+
+// Renames a board that could not be read to kanban.json.broken-20211224-180000.000,
+// its journal goes along to kanban.json.journal.broken-20211224-180000.000. The
+// files of a Markdown board go to board.broken-20211224-180000.000/
+pub fn move_aside(path: &Path) -> io::Result<PathBuf> {
+    let suffix = format!(".broken-{}", Local::now().format(BACKUP_TIME_FORMAT));
+    let broken = sibling_path(path, &suffix);
+
+    if format_of(path) == Format::Markdown {
+        markdown::move_board_files(path, &broken)?;
+
+        return Ok(broken);
+    }
+
+    if path.exists() {
+        fs::rename(path, &broken)?;
+    }
+
+    set_journal_aside(path, &suffix)?;
+
+    Ok(broken)
+}
+
+fn set_journal_aside(path: &Path, suffix: &str) -> io::Result<()> {
+    let journal = journal::journal_path(path);
+
+    if journal.is_file() {
+        fs::rename(&journal, sibling_path(&journal, suffix))?;
+    }
+
+    Ok(())
+}
+
+// Rewrites the board and its backups encrypted with `new`, or in plain text
+// without it. Returns the backups `old` could not decrypt, they are left as they are
+pub fn change_encryption(path: &Path, old: Option<&Cipher>, new: Option<&Cipher>) -> io::Result<Vec<PathBuf>> {
+    let mut skipped = Vec::new();
+
+    // only the snapshot is rewritten, so the journal goes into it first
+    JsonStorage::new(path, old.cloned()).compact()?;
+
+    let mut files = vec![path.to_path_buf()];
+    files.extend(list_backups(path)?.into_iter().map(|backup| backup.path));
+
+    for (i, file) in files.into_iter().enumerate() {
+        // the journal of a backup is folded into it the same way, without a backup of the backup
+        if journal::journal_path(&file).is_file() {
+            match JsonStorage::new(&file, old.cloned()).read() {
+                Ok(board) => {
+                    let content = schema::to_json(&board.projects, &board.daily_tasks, &board.labels)?;
+
+                    match new {
+                        Some(new) => write_atomic(&file, &new.encrypt(content.as_bytes()).map_err(io::Error::other)?)?,
+                        None => write_atomic(&file, content.as_bytes())?
+                    }
+
+                    journal::clear(&file)?;
+                },
+                Err(_) => skipped.push(file)
+            }
+
+            continue;
+        }
+
+        let content = fs::read(&file)?;
+
+        let plain = match (crypto::is_encrypted(&content), old) {
+            (false, _) => content,
+            (true, Some(old)) => match old.decrypt(&content) {
+                Ok(plain) => plain,
+                // the board itself has to open
+                Err(e) if i == 0 => return Err(io::Error::other(e)),
+                Err(_) => {
+                    skipped.push(file);
+                    continue;
+                }
+            },
+            (true, None) => {
+                skipped.push(file);
+                continue;
+            }
+        };
+
+        match new {
+            Some(new) => write_atomic(&file, &new.encrypt(&plain).map_err(io::Error::other)?)?,
+            None => write_atomic(&file, &plain)?
+        }
+    }
+
+    Ok(skipped)
+}
+
+// * Saving
+// Replaces the board file without ever leaving a half written one behind:
+// the current file is backed up, the new content goes to a temp file that is
+// synced to disk and then renamed over the board
+pub fn save_with_backup(path: &Path, content: &[u8]) -> io::Result<()> {
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    if path.is_file() {
+        backup(path)?;
+    }
+
+    write_atomic(path, content)
+}
+
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let temp_path = sibling_path(path, ".tmp");
+
+    let result = (|| {
+        let mut temp = fs::File::create(&temp_path)?;
+        temp.write_all(content)?;
+        temp.sync_all()?;
+
+        fs::rename(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result?;
+    sync_dir(path)
+}
+
+// The rename is only durable once the directory entry is on disk
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::File::open(dir)?.sync_all(),
+        _ => fs::File::open(".")?.sync_all()
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+
+    Ok(())
+}
+
+// "kanban.json" -> "kanban.json<suffix>" in the same directory
+pub fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+
+    path.with_file_name(name)
+}
+
+// * Backups
+// kanban.json -> kanban.json.backups/kanban-20211224-180000.000.json, the
+// journal next to it goes to kanban-20211224-180000.000.json.journal so the
+// backup is the whole board as it was before the save
+pub fn backup_dir(path: &Path) -> PathBuf {
+
+    sibling_path(path, ".backups")
+}
+
+fn backup(path: &Path) -> io::Result<()> {
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir)?;
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let name = format!("{}-{}{}", stem, Local::now().format(BACKUP_TIME_FORMAT), extension);
+
+    fs::copy(path, dir.join(&name))?;
+
+    let journal = journal::journal_path(path);
+
+    if journal.is_file() {
+        fs::copy(&journal, journal::journal_path(&dir.join(&name)))?;
+    }
+
+    // drop the oldest ones
+    for old in list_backups(path)?.into_iter().skip(BACKUP_COUNT) {
+        fs::remove_file(&old.path)?;
+        journal::clear(&old.path)?;
+    }
+
+    Ok(())
+}
+
+pub struct Backup {
+    pub path: PathBuf,
+    pub modified: DateTime<Local>,
+    // of the snapshot and its journal
+    pub size: u64
+}
+
+// Newest first, journals are part of the backup they belong to
+pub fn list_backups(path: &Path) -> io::Result<Vec<Backup>> {
+    let dir = backup_dir(path);
+
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        let is_journal = entry.path().to_string_lossy().ends_with(".journal");
+
+        if metadata.is_file() && !is_journal {
+            let journal_size = fs::metadata(journal::journal_path(&entry.path())).map(|journal| journal.len()).unwrap_or(0);
+
+            backups.push(Backup {
+                path: entry.path(),
+                modified: DateTime::from(metadata.modified()?),
+                size: metadata.len() + journal_size
+            });
+        }
+    }
+
+    // the names sort by their timestamp
+    backups.sort_by(|a, b| b.path.cmp(&a.path));
+
+    Ok(backups)
+}
+
+// Puts a backup and its journal back in place, the board it replaces is
+// backed up with its journal as well
+pub fn restore_backup(path: &Path, backup: &Backup) -> io::Result<()> {
+    let content = fs::read(&backup.path)?;
+
+    let journal = match fs::read(journal::journal_path(&backup.path)) {
+        Ok(journal) => Some(journal),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e)
+    };
+
+    save_with_backup(path, &content)?;
+
+    match journal {
+        Some(journal) => write_atomic(&journal::journal_path(path), &journal),
+        None => journal::clear(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journaled_saves_keep_a_version_once_in_a_while() {
+        let dir = std::env::temp_dir().join(format!("kanban-storage-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("kanban.json");
+
+        let mut storage = JsonStorage::new(&path, None);
+        let mut projects = vec![KanbanProject::new(String::from("Website"))];
+
+        // backups are named by the millisecond
+        let wait = || std::thread::sleep(std::time::Duration::from_millis(5));
+
+        for name in ["Blog", "Shop", "Docs"] {
+            storage.save(&projects, &[], &Labels::new()).unwrap();
+            projects.push(KanbanProject::new(String::from(name)));
+            wait();
+        }
+
+        // the first save created the board, only the first of the two journaled
+        // on top of it kept the version before it
+        assert_eq!(list_backups(&path).unwrap().len(), 1);
+
+        // another program keeps its own time
+        let mut other = JsonStorage::new(&path, None);
+        other.load().ok().unwrap();
+        other.save(&projects[..1], &[], &Labels::new()).unwrap();
+
+        let backups = list_backups(&path).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert!(journal::journal_path(&backups[0].path).is_file());
+        wait();
+
+        restore_backup(&path, &backups[0]).unwrap();
+        let restored = JsonStorage::new(&path, None).load().ok().unwrap();
+
+        assert_eq!(restored.projects.len(), 3);
+        assert_eq!(list_backups(&path).unwrap().len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
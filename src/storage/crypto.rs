@@ -0,0 +1,110 @@
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    aead::rand_core::RngCore,
+    Key, XChaCha20Poly1305, XNonce
+};
+
+// * Encrypted board files
+// MAGIC, a random salt for the key derivation, a random nonce and the
+// XChaCha20-Poly1305 ciphertext of the JSON board. The key is derived from the
+// passphrase with Argon2id, MAGIC and the salt are authenticated as well
+const MAGIC: &[u8] = b"KANBAN-ENCRYPTED-1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Clone)]
+pub struct Cipher {
+    passphrase: String,
+    salt: [u8; SALT_LEN],
+    key: Key
+}
+
+pub fn is_encrypted(content: &[u8]) -> bool {
+
+    content.starts_with(MAGIC)
+}
+
+impl Cipher {
+
+    // For encrypting a board the first time, with a new salt
+    pub fn new(passphrase: &str) -> Result<Cipher, String> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        Cipher::with_salt(passphrase, salt)
+    }
+
+    // For an encrypted file, fails when the passphrase does not open it
+    pub fn for_file(passphrase: &str, content: &[u8]) -> Result<Cipher, String> {
+        let (salt, _, _) = split(content)?;
+        let cipher = Cipher::with_salt(passphrase, salt)?;
+
+        cipher.decrypt(content)?;
+
+        Ok(cipher)
+    }
+
+    fn with_salt(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Cipher, String> {
+        let mut key = Key::default();
+
+        Argon2::default().hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| format!("deriving the key failed: {}", e))?;
+
+        Ok(Cipher {
+            passphrase: String::from(passphrase),
+            salt,
+            key
+        })
+    }
+
+    pub fn encrypt(&self, plain: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&self.salt);
+
+        let ciphertext = XChaCha20Poly1305::new(&self.key)
+                            .encrypt(&nonce, Payload { msg: plain, aad: &header })
+                            .map_err(|_| String::from("encrypting the board failed"))?;
+
+        let mut content = header;
+        content.extend_from_slice(&nonce);
+        content.extend_from_slice(&ciphertext);
+
+        Ok(content)
+    }
+
+    pub fn decrypt(&self, content: &[u8]) -> Result<Vec<u8>, String> {
+        let (salt, nonce, ciphertext) = split(content)?;
+
+        // e.g. a backup from before the passphrase was changed to the same one again
+        let other;
+        let cipher = if salt == self.salt {
+            self
+        } else {
+            other = Cipher::with_salt(&self.passphrase, salt)?;
+            &other
+        };
+
+        XChaCha20Poly1305::new(&cipher.key)
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: &content[..MAGIC.len() + SALT_LEN] })
+            .map_err(|_| String::from("wrong passphrase or the file was modified"))
+    }
+}
+
+// salt, nonce and ciphertext
+type Parts<'a> = ([u8; SALT_LEN], &'a [u8], &'a [u8]);
+
+fn split(content: &[u8]) -> Result<Parts<'_>, String> {
+
+    if !is_encrypted(content) || content.len() < MAGIC.len() + SALT_LEN + NONCE_LEN {
+        return Err(String::from("not an encrypted board"));
+    }
+
+    let rest = &content[MAGIC.len()..];
+    let mut salt = [0u8; SALT_LEN];
+    salt.copy_from_slice(&rest[..SALT_LEN]);
+
+    Ok((salt, &rest[SALT_LEN..SALT_LEN + NONCE_LEN], &rest[SALT_LEN + NONCE_LEN..]))
+}
@@ -0,0 +1,486 @@
+use std::{
+    fs, io,
+
+    collections::HashMap,
+
+    fs::OpenOptions,
+    io::Write,
+
+    path::{Path, PathBuf}
+};
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    crypto::{self, Cipher},
+    rows::{changes, ProjectRow, Rows},
+    sibling_path, LoadError
+};
+
+// Entries after which the next save writes a new snapshot instead
+pub const COMPACT_AFTER: usize = 1000;
+
+// * Journal of the JSON backend
+// kanban.json is a snapshot and kanban.json.journal holds every change made
+// since, one JSON line per added, edited, moved or deleted row:
+//
+//   {"at":"2021-12-24T18:00:00+01:00","op":"edit","kind":"column","project":"...","position":2,"data":{...,"cards":["..."]}}
+//
+// Each entry holds the whole row at its new place, so replaying an entry that
+// is already in the snapshot changes nothing. Moving a card is a move of the
+// card with the names of the columns it left and went to, and an edit of both
+// columns. Lines of an encrypted board are encrypted one by one and written as hex
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Local>,
+    pub op: Op,
+    #[serde(flatten)]
+    pub target: Target,
+    // the row after the change, or before it for a delete
+    pub data: Value
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Add,
+    Edit,
+    Move,
+    Delete
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Target {
+    Project { id: String, position: i64 },
+    Column { project: String, position: i64 },
+    Card {
+        id: String,
+        // columns a moved card left and went to
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to: Option<String>
+    },
+    DailyTask { position: i64 },
+    Labels
+}
+
+pub struct Journal {
+    pub entries: Vec<Entry>,
+    // the last line was cut off, e.g. by a crash while it was written
+    pub torn: bool
+}
+
+pub fn journal_path(board: &Path) -> PathBuf {
+
+    sibling_path(board, ".journal")
+}
+
+impl Op {
+
+    pub fn name(&self) -> &'static str {
+
+        match self {
+            Op::Add => "add",
+            Op::Edit => "edit",
+            Op::Move => "move",
+            Op::Delete => "delete"
+        }
+    }
+}
+
+impl Entry {
+
+    // e.g. "card Fix the header", for listing the journal
+    pub fn describe(&self) -> String {
+        let field = |key: &str| self.data.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+
+        match &self.target {
+            Target::Project { .. } => format!("project {}", field("name")),
+            Target::Column { .. } => format!("column  {}", field("name")),
+            Target::Card { from: Some(from), to: Some(to), .. } => format!("card    {} ({} -> {})", field("title"), from, to),
+            Target::Card { .. } => format!("card    {}", field("title")),
+            Target::DailyTask { .. } => format!("task    {}", field("task_name")),
+            Target::Labels => String::from("labels")
+        }
+    }
+}
+
+// What changed between two versions of the board
+pub fn entries(old: &Rows, new: &Rows) -> serde_json::Result<Vec<Entry>> {
+    let at = Local::now();
+    let mut entries = Vec::new();
+
+    let mut push = |op: Op, target: Target, data: &str| -> serde_json::Result<()> {
+        entries.push(Entry { at, op, target, data: serde_json::from_str(data)? });
+
+        Ok(())
+    };
+
+    let (mut changed, mut removed) = changes(&old.projects, &new.projects);
+    sort(&mut changed, &mut removed);
+
+    for id in removed {
+        let row = &old.projects[id];
+        push(Op::Delete, Target::Project { id: id.clone(), position: row.position }, &row.data)?;
+    }
+    for (id, row) in changed {
+        let op = match old.projects.get(id) {
+            Some(old_row) if old_row.position != row.position => Op::Move,
+            Some(_) => Op::Edit,
+            None => Op::Add
+        };
+
+        push(op, Target::Project { id: id.clone(), position: row.position }, &row.data)?;
+    }
+
+    // cards are added before the columns holding them and deleted after, so
+    // the entries before a torn one never leave a column without its card
+    let (mut changed_cards, mut removed_cards) = changes(&old.cards, &new.cards);
+    sort(&mut changed_cards, &mut removed_cards);
+
+    for (id, data) in changed_cards {
+        let (op, from, to) = match old.cards.get(id) {
+            Some(old_data) => match moved(old_data, data) {
+                Some((from, to)) => (Op::Move, Some(from), Some(to)),
+                None => (Op::Edit, None, None)
+            },
+            None => (Op::Add, None, None)
+        };
+
+        push(op, Target::Card { id: id.clone(), from, to }, data)?;
+    }
+
+    // columns are rows by their place, deleting one leaves the last place of
+    // its project empty and moves the ones right of it. The entries name the
+    // column that was really deleted and the ones that only moved
+    let (mut changed, mut removed) = changes(&old.columns, &new.columns);
+    sort(&mut changed, &mut removed);
+
+    let mut gone = replaced(&old.columns, &new.columns).into_iter();
+
+    for key in removed {
+        let data = gone.next().unwrap_or(&old.columns[key]);
+
+        push(Op::Delete, Target::Column { project: key.0.clone(), position: key.1 }, data)?;
+    }
+
+    let added = replaced(&new.columns, &old.columns);
+
+    for (key, data) in changed {
+        let op = if added.contains(&data) {
+            Op::Add
+        } else if has_column(&old.columns, &key.0, data) && old.columns.get(key).map(|old_data| column_name(old_data)) != Some(column_name(data)) {
+            Op::Move
+        } else if old.columns.contains_key(key) {
+            Op::Edit
+        } else {
+            Op::Add
+        };
+
+        push(op, Target::Column { project: key.0.clone(), position: key.1 }, data)?;
+    }
+
+    for id in removed_cards {
+        push(Op::Delete, Target::Card { id: id.clone(), from: None, to: None }, &old.cards[id])?;
+    }
+
+    let (mut changed, mut removed) = changes(&old.daily_tasks, &new.daily_tasks);
+    sort(&mut changed, &mut removed);
+
+    for position in removed {
+        push(Op::Delete, Target::DailyTask { position: *position }, &old.daily_tasks[position])?;
+    }
+    for (position, data) in changed {
+        let op = if old.daily_tasks.contains_key(position) {Op::Edit} else {Op::Add};
+
+        push(op, Target::DailyTask { position: *position }, data)?;
+    }
+
+    if let (Some(labels), true) = (&new.labels, old.labels != new.labels) {
+        push(if old.labels.is_some() {Op::Edit} else {Op::Add}, Target::Labels, labels)?;
+    }
+
+    Ok(entries)
+}
+
+// The columns the card left and went to when it was moved since `old`, a move
+// adds a transition to the card
+fn moved(old: &str, new: &str) -> Option<(String, String)> {
+    let transitions = |data: &str| serde_json::from_str::<Value>(data).ok()
+                                    .and_then(|card| card.get("transitions").and_then(Value::as_array).cloned())
+                                    .unwrap_or_default();
+
+    let (old, new) = (transitions(old), transitions(new));
+    let column = |transition: Option<&Value>, key: &str| transition.and_then(|t| t.get(key)).and_then(Value::as_str).map(String::from);
+
+    match (column(new.get(old.len()), "from"), column(new.last(), "to")) {
+        (Some(from), Some(to)) if new.len() > old.len() && from != to => Some((from, to)),
+        _ => None
+    }
+}
+
+// Columns of `from` whose name is in no place of their project in `to`, and
+// whose place there is empty or taken by a column that moved, in the order of their places
+fn replaced<'a>(from: &'a HashMap<(String, i64), String>, to: &HashMap<(String, i64), String>) -> Vec<&'a String> {
+    let mut columns: Vec<_> = from.iter()
+        .filter(|(key, data)| !has_column(to, &key.0, data))
+        .filter(|(key, _)| to.get(*key).is_none_or(|taken| has_column(from, &key.0, taken)))
+        .collect();
+
+    columns.sort_by(|a, b| a.0.cmp(b.0));
+    columns.into_iter().map(|(_, data)| data).collect()
+}
+
+// Columns are told apart by their name, their cards change with every move
+fn has_column(columns: &HashMap<(String, i64), String>, project: &String, data: &str) -> bool {
+
+    columns.iter().any(|(key, other)| &key.0 == project && column_name(other) == column_name(data))
+}
+
+fn column_name(data: &str) -> Option<String> {
+
+    serde_json::from_str::<Value>(data).ok().and_then(|column| column.get("name").and_then(Value::as_str).map(String::from))
+}
+
+// In the order of the keys, so the journal reads the same way every time
+fn sort<K: Ord, V>(changed: &mut [(&K, &V)], removed: &mut [&K]) {
+
+    changed.sort_by(|a, b| a.0.cmp(b.0));
+    removed.sort();
+}
+
+pub fn replay(rows: &mut Rows, entry: &Entry) {
+    let data = entry.data.to_string();
+
+    match (&entry.target, entry.op) {
+        (Target::Project { id, .. }, Op::Delete) => {
+            rows.projects.remove(id);
+        },
+        (Target::Project { id, position }, _) => {
+            rows.projects.insert(id.clone(), ProjectRow { position: *position, data });
+        },
+
+        (Target::Column { project, position }, Op::Delete) => {
+            rows.columns.remove(&(project.clone(), *position));
+        },
+        (Target::Column { project, position }, _) => {
+            rows.columns.insert((project.clone(), *position), data);
+        },
+
+        (Target::Card { id, .. }, Op::Delete) => {
+            rows.cards.remove(id);
+        },
+        (Target::Card { id, .. }, _) => {
+            rows.cards.insert(id.clone(), data);
+        },
+
+        (Target::DailyTask { position }, Op::Delete) => {
+            rows.daily_tasks.remove(position);
+        },
+        (Target::DailyTask { position }, _) => {
+            rows.daily_tasks.insert(*position, data);
+        },
+
+        (Target::Labels, Op::Delete) => {
+            rows.labels = None;
+        },
+        (Target::Labels, _) => {
+            rows.labels = Some(data);
+        }
+    }
+}
+
+// A missing journal has no entries
+pub fn read(board: &Path, cipher: Option<&Cipher>) -> Result<Journal, LoadError> {
+    let path = journal_path(board);
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(LoadError::Io(e))
+    };
+
+    let mut journal = Journal { entries: Vec::new(), torn: false };
+    let lines: Vec<&str> = content.lines().filter(|line| !line.trim().is_empty()).collect();
+
+    for (i, line) in lines.iter().enumerate() {
+        match parse_line(line, cipher) {
+            Ok(entry) => journal.entries.push(entry),
+            Err(LoadError::Encryption(e)) => return Err(LoadError::Encryption(e)),
+            // nothing was written after it, the save it belongs to never finished
+            Err(_) if i + 1 == lines.len() && !content.ends_with('\n') => journal.torn = true,
+            Err(e) => return Err(LoadError::Schema(format!("{} line {}: {}", path.display(), i + 1, e.message())))
+        }
+    }
+
+    Ok(journal)
+}
+
+// A plain line in the journal of an encrypted board was not written by it
+fn parse_line(line: &str, cipher: Option<&Cipher>) -> Result<Entry, LoadError> {
+
+    if line.starts_with('{') && cipher.is_some() {
+        return Err(LoadError::Encryption(String::from("the journal of an encrypted board has a plain entry")));
+    }
+    if line.starts_with('{') {
+        return serde_json::from_str(line).map_err(LoadError::Parse);
+    }
+
+    let content = from_hex(line).ok_or_else(|| LoadError::Schema(String::from("neither JSON nor an encrypted entry")))?;
+
+    let plain = match cipher {
+        Some(cipher) if crypto::is_encrypted(&content) => cipher.decrypt(&content).map_err(LoadError::Encryption)?,
+        Some(_) => return Err(LoadError::Schema(String::from("not an encrypted entry"))),
+        None => return Err(LoadError::Encryption(String::from("the journal is encrypted")))
+    };
+
+    serde_json::from_slice(&plain).map_err(LoadError::Parse)
+}
+
+// The entries of one save are written at once and synced before the save counts as done
+pub fn append(board: &Path, entries: &[Entry], cipher: Option<&Cipher>) -> io::Result<()> {
+    let mut content = String::new();
+
+    for entry in entries {
+        let line = serde_json::to_string(entry)?;
+
+        match cipher {
+            Some(cipher) => content.push_str(&to_hex(&cipher.encrypt(line.as_bytes()).map_err(io::Error::other)?)),
+            None => content.push_str(&line)
+        }
+
+        content.push('\n');
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(journal_path(board))?;
+    file.write_all(content.as_bytes())?;
+
+    file.sync_data()
+}
+
+// After a new snapshot was written
+pub fn clear(board: &Path) -> io::Result<()> {
+
+    match fs::remove_file(journal_path(board)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(())
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::{Card, Kanban, KanbanProject, Labels, Task},
+        schema::SCHEMA_VERSION
+    };
+
+    #[test]
+    fn replaying_the_entries_gives_the_new_board() {
+        let mut project = KanbanProject::new(String::from("Website"));
+
+        for title in ["Fix the header", "Write the docs", "Set up CI"] {
+            project.columns[0].cards.push(Card::new(title));
+        }
+
+        let mut projects = vec![project];
+        let mut daily_tasks = vec![Task::new("Standup")];
+        let mut labels = Labels::new();
+
+        let old = Rows::from_board(&projects, &daily_tasks, &labels).unwrap();
+        let mut replayed = Rows::from_board(&projects, &daily_tasks, &labels).unwrap();
+
+        // a move, an edit and a delete, a new project, and the rest of the board
+        let moved = projects[0].columns[0].cards.remove(0);
+        projects[0].columns[2].cards.push(moved);
+        projects[0].columns[0].cards[0].title = String::from("Write the README");
+        projects[0].columns[0].cards.pop();
+        projects.push(KanbanProject::new(String::from("Shop")));
+        daily_tasks.clear();
+        labels.insert(String::from("bug"), tui::style::Color::Red);
+
+        let new = Rows::from_board(&projects, &daily_tasks, &labels).unwrap();
+
+        for entry in entries(&old, &new).unwrap() {
+            replay(&mut replayed, &entry);
+        }
+
+        assert_eq!(replayed.to_document(SCHEMA_VERSION).ok().unwrap(), new.to_document(SCHEMA_VERSION).ok().unwrap());
+    }
+
+    #[test]
+    fn moves_and_deleted_columns_are_named() {
+        let mut kanban = Kanban::default();
+        kanban.add_project(KanbanProject::new(String::from("Website")));
+        kanban.projects[0].columns[0].cards.push(Card::new("Fix the header"));
+
+        let old = Rows::from_board(&kanban.projects, &[], &kanban.labels).unwrap();
+
+        kanban.move_card(2);
+        kanban.column_index = 1;
+        kanban.delete_column();
+
+        let new = Rows::from_board(&kanban.projects, &[], &kanban.labels).unwrap();
+        let described: Vec<String> = entries(&old, &new).unwrap().iter()
+                                        .map(|entry| format!("{} {}", entry.op.name(), entry.describe()))
+                                        .collect();
+
+        assert!(described.contains(&String::from("move card    Fix the header (ToDo -> Done)")));
+        assert!(described.contains(&String::from("delete column  In Progress")));
+        assert!(described.contains(&String::from("move column  Done")));
+        assert!(!described.iter().any(|entry| entry.starts_with("delete column  Done")));
+    }
+
+    #[test]
+    fn a_torn_last_line_is_dropped() {
+        let dir = std::env::temp_dir().join(format!("kanban-journal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let board = dir.join("kanban.json");
+
+        let projects = vec![KanbanProject::new(String::from("Website"))];
+        let new = Rows::from_board(&projects, &[], &Labels::new()).unwrap();
+        let written = entries(&Rows::default(), &new).unwrap();
+
+        append(&board, &written, None).unwrap();
+
+        let mut file = OpenOptions::new().append(true).open(journal_path(&board)).unwrap();
+        file.write_all(br#"{"at":"2021-12-24T18:00:00+01:00","op":"add","kind":"#).unwrap();
+
+        let journal = read(&board, None).ok().unwrap();
+
+        assert!(journal.torn);
+        assert_eq!(journal.entries.len(), written.len());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_journals_take_no_plain_entries() {
+        let cipher = Cipher::new("passphrase").unwrap();
+        let line = r#"{"at":"2021-12-24T18:00:00+01:00","op":"add","kind":"daily_task","position":0,"data":{}}"#;
+
+        assert!(parse_line(line, None).is_ok());
+        assert!(matches!(parse_line(line, Some(&cipher)), Err(LoadError::Encryption(_))));
+
+        let encrypted = to_hex(&cipher.encrypt(line.as_bytes()).unwrap());
+        assert!(parse_line(&encrypted, Some(&cipher)).is_ok());
+    }
+}
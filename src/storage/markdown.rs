@@ -0,0 +1,685 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+
+    path::{Path, PathBuf}
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    data::{KanbanProject, Labels, Task},
+    schema::SCHEMA_VERSION
+};
+
+use super::{decode, empty_board, write_atomic, LoadError, LoadedBoard, Stamp, Storage};
+
+// * Markdown directory backend
+// A board is a directory with one Markdown file per project:
+//
+//   ---
+//   schema_version: 1
+//   id: 18df8cdf128f3c99-1a80-0001
+//   next_card_number: 3
+//   ---
+//
+//   # Website
+//
+//   ## ToDo
+//
+//   wip_limit: 3
+//
+//   - Fix the header
+//     number: 1
+//     labels:
+//       - bug
+//     - [ ] checklist item
+//     > description
+//
+// Fields are written one per line so a change shows up as a one line diff.
+// Other Markdown files, e.g. docs with front matter of their own, are left
+// alone: a project file has schema_version and id in its front matter. The
+// daily tasks and the label palette are kept in files of their own
+pub struct MarkdownStorage {
+    path: PathBuf
+}
+
+impl MarkdownStorage {
+
+    pub fn new(path: &Path) -> MarkdownStorage {
+
+        MarkdownStorage {
+            path: path.to_path_buf()
+        }
+    }
+}
+
+// No project ever gets one of these names
+const DAILY_TASKS_FILE: &str = "daily-tasks.md";
+const LABELS_FILE: &str = "labels.md";
+
+impl Storage for MarkdownStorage {
+
+    fn load(&mut self) -> Result<LoadedBoard, LoadError> {
+
+        if !self.path.exists() {
+            return Ok(empty_board());
+        }
+
+        let mut projects: Vec<Value> = Vec::new();
+        let mut daily_tasks: Vec<Value> = Vec::new();
+        let mut labels = Map::new();
+        let mut schema_version = None;
+
+        for (file, text) in project_files(&self.path).map_err(LoadError::Io)? {
+            let parsed = if file.file_name().is_some_and(|name| name == DAILY_TASKS_FILE) {
+                parse_daily_tasks(&text).map(|(tasks, version)| {
+                    daily_tasks = tasks;
+                    version
+                })
+            } else if file.file_name().is_some_and(|name| name == LABELS_FILE) {
+                parse_labels(&text).map(|(palette, version)| {
+                    labels = palette;
+                    version
+                })
+            } else {
+                parse_project(&text).map(|(project, version)| {
+                    projects.push(project);
+                    version
+                })
+            };
+
+            let version = parsed.map_err(|e| LoadError::Schema(format!("{}: {}", file.display(), e)))?;
+
+            // a single newer file makes the whole board newer
+            schema_version = schema_version.max(Some(version));
+        }
+
+        // ids start with their creation time
+        projects.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+
+        let document = json!({
+            "schema_version": schema_version.unwrap_or(SCHEMA_VERSION),
+            "projects": projects,
+            "daily_tasks": daily_tasks,
+            "labels": labels
+        });
+
+        decode(document, None)
+    }
+
+    fn save(&mut self, projects: &[KanbanProject], daily_tasks: &[Task], labels: &Labels) -> io::Result<()> {
+        fs::create_dir_all(&self.path)?;
+
+        let existing: Vec<PathBuf> = project_files(&self.path)?.into_iter().map(|(file, _)| file).collect();
+        let daily_tasks_file = self.path.join(DAILY_TASKS_FILE);
+        let mut written: HashSet<PathBuf> = HashSet::new();
+
+        for project in projects {
+            let file = free_file_name(&self.path, &project.name, &written, &existing);
+            write_if_changed(&file, &write_project(project)?)?;
+            written.insert(file);
+        }
+
+        if !daily_tasks.is_empty() {
+            write_if_changed(&daily_tasks_file, &write_daily_tasks(daily_tasks)?)?;
+            written.insert(daily_tasks_file);
+        }
+
+        if !labels.is_empty() {
+            let labels_file = self.path.join(LABELS_FILE);
+
+            write_if_changed(&labels_file, &write_labels(labels)?)?;
+            written.insert(labels_file);
+        }
+
+        // deleted and renamed projects
+        for file in existing {
+            if !written.contains(&file) {
+                fs::remove_file(file)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn stamp(&self) -> Stamp {
+
+        Stamp::of(markdown_files(&self.path).unwrap_or_default())
+    }
+}
+
+fn markdown_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && path.extension().is_some_and(|e| e == "md") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+// Untouched projects keep their files as they are
+fn write_if_changed(file: &Path, content: &str) -> io::Result<()> {
+
+    if fs::read_to_string(file).ok().as_deref() != Some(content) {
+        write_atomic(file, content.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+// Markdown files of the directory the board wrote, only these are read and
+// removed again
+fn project_files(dir: &Path) -> io::Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+
+    for path in markdown_files(dir)? {
+        let text = fs::read_to_string(&path)?;
+        let reserved = path.file_name().is_some_and(|name| name == DAILY_TASKS_FILE || name == LABELS_FILE);
+
+        if is_board_file(&text, reserved) {
+            files.push((path, text));
+        }
+    }
+
+    Ok(files)
+}
+
+// Files of a board that could not be read go to `to`, other Markdown files
+// stay where they are
+pub fn move_board_files(dir: &Path, to: &Path) -> io::Result<()> {
+
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(to)?;
+
+    for (file, _) in project_files(dir)? {
+        fs::rename(&file, to.join(file.file_name().unwrap_or_default()))?;
+    }
+
+    Ok(())
+}
+
+// Front matter with schema_version, and the id of the project unless it is one of the reserved files
+fn is_board_file(text: &str, reserved: bool) -> bool {
+    let mut lines = text.lines();
+
+    if lines.next() != Some("---") {
+        return false;
+    }
+
+    let keys: Vec<&str> = lines.take_while(|line| *line != "---")
+                            .filter_map(|line| line.split_once(':').map(|(key, _)| key))
+                            .collect();
+
+    keys.contains(&"schema_version") && (reserved || keys.contains(&"id"))
+}
+
+// "My Project" -> my-project.md, other Markdown files in the directory are never overwritten
+fn free_file_name(dir: &Path, name: &str, taken: &HashSet<PathBuf>, projects: &[PathBuf]) -> PathBuf {
+    let mut slug = String::new();
+
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = match slug.trim_end_matches('-') {
+        "" => "project",
+        slug => slug
+    };
+
+    let mut count = 1;
+
+    loop {
+        let file = match count {
+            1 => dir.join(format!("{}.md", slug)),
+            _ => dir.join(format!("{}-{}.md", slug, count))
+        };
+
+        let foreign = file.exists() && !projects.contains(&file);
+        let reserved = file.file_name().is_some_and(|name| name == DAILY_TASKS_FILE || name == LABELS_FILE);
+
+        if !taken.contains(&file) && !foreign && !reserved {
+            return file;
+        }
+
+        count += 1;
+    }
+}
+
+// * Writing
+fn write_project(project: &KanbanProject) -> serde_json::Result<String> {
+    let mut out = String::from("---\n");
+
+    write_field(&mut out, "", "schema_version", &json!(SCHEMA_VERSION));
+    write_fields(&mut out, "", &serde_json::to_value(project)?, &["name", "columns"]);
+
+    out.push_str("---\n\n");
+    out.push_str(&format!("# {}\n", text(&project.name)));
+
+    for column in &project.columns {
+        out.push_str(&format!("\n## {}\n", text(&column.name)));
+
+        let mut fields = String::new();
+        write_fields(&mut fields, "", &serde_json::to_value(column)?, &["name", "cards"]);
+
+        if !fields.is_empty() {
+            out.push('\n');
+            out.push_str(&fields);
+        }
+
+        for card in &column.cards {
+            out.push_str(&format!("\n- {}\n", text(&card.title)));
+
+            write_fields(&mut out, "  ", &serde_json::to_value(card)?, &["title", "description", "checklist"]);
+
+            for item in &card.checklist {
+                out.push_str(&format!("  - [{}] {}\n", if item.done {"x"} else {" "}, text(&item.step_name)));
+            }
+
+            if !card.description.is_empty() {
+                for line in card.description.split('\n') {
+                    match line {
+                        "" => out.push_str("  >\n"),
+                        line => out.push_str(&format!("  > {}\n", line))
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+// Empty fields are left out, they read back as their default
+fn write_daily_tasks(daily_tasks: &[Task]) -> serde_json::Result<String> {
+    let mut out = String::from("---\n");
+
+    write_field(&mut out, "", "schema_version", &json!(SCHEMA_VERSION));
+
+    out.push_str("---\n\n# Daily Tasks\n");
+
+    for task in daily_tasks {
+        out.push_str(&format!("\n- {}\n", text(&task.task_name)));
+        write_fields(&mut out, "  ", &serde_json::to_value(task)?, &["task_name"]);
+    }
+
+    Ok(out)
+}
+
+// One `name: colour` line per label
+fn write_labels(labels: &Labels) -> serde_json::Result<String> {
+    let mut out = String::from("---\n");
+
+    write_field(&mut out, "", "schema_version", &json!(SCHEMA_VERSION));
+
+    out.push_str("---\n\n# Labels\n\n");
+
+    for (label, color) in labels {
+        write_field(&mut out, "", &text(label), &serde_json::to_value(color)?);
+    }
+
+    Ok(out)
+}
+
+fn write_fields(out: &mut String, indent: &str, value: &Value, skip: &[&str]) {
+
+    if let Value::Object(map) = value {
+        for (key, value) in map {
+            let empty = match value {
+                Value::Null => true,
+                Value::Array(items) => items.is_empty(),
+                Value::Object(map) => map.is_empty(),
+                _ => false
+            };
+
+            if !empty && !skip.contains(&key.as_str()) {
+                write_field(out, indent, key, value);
+            }
+        }
+    }
+}
+
+// Lists get one line per item, everything else fits on the line of its key
+fn write_field(out: &mut String, indent: &str, key: &str, value: &Value) {
+
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str(&format!("{}{}:\n", indent, key));
+
+            for item in items {
+                out.push_str(&format!("{}  - {}\n", indent, scalar(item)));
+            }
+        },
+        value => out.push_str(&format!("{}{}: {}\n", indent, key, scalar(value)))
+    }
+}
+
+// Strings are written bare unless they would read back as something else
+fn scalar(value: &Value) -> String {
+
+    match value {
+        Value::String(s) if !s.is_empty() && s.trim() == s && !s.contains('\n')
+            && serde_json::from_str::<Value>(s).is_err() => s.clone(),
+        value => value.to_string()
+    }
+}
+
+fn parse_scalar(text: &str) -> Value {
+
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(String::from(text)))
+}
+
+// Titles and names are the rest of their line, one with a line break, or
+// starting with a quote, is written as a JSON string
+fn text(s: &str) -> String {
+
+    if s.contains(['\n', '\r']) || s.starts_with('"') {
+        Value::from(s).to_string()
+    } else {
+        String::from(s)
+    }
+}
+
+fn parse_text(text: &str) -> String {
+
+    match serde_json::from_str(text) {
+        Ok(Value::String(s)) if text.starts_with('"') => s,
+        _ => String::from(text)
+    }
+}
+
+// * Reading
+// The project as the JSON document has it and the schema it was written with
+fn parse_project(text: &str) -> Result<(Value, u32), String> {
+    let mut lines = text.lines().enumerate().map(|(n, line)| (n + 1, line));
+
+    let (mut project, schema_version) = read_front_matter(&mut lines)?;
+    let mut columns: Vec<Map<String, Value>> = Vec::new();
+    let mut list: Option<String> = None;
+    let mut in_card = false;
+    // the first description line of the card was read
+    let mut described = false;
+
+    for (n, line) in lines {
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("## ") {
+            columns.push(json_map(json!({ "name": parse_text(name), "cards": [] })));
+            in_card = false;
+            list = None;
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("# ") {
+            project.insert(String::from("name"), json!(parse_text(name)));
+            continue;
+        }
+
+        let column = columns.last_mut().ok_or_else(|| at(n, String::from("expected a ## column heading")))?;
+
+        if let Some(title) = line.strip_prefix("- ").or(if line == "-" {Some("")} else {None}) {
+            if let Some(Value::Array(cards)) = column.get_mut("cards") {
+                cards.push(json!({ "title": parse_text(title), "description": "", "checklist": [] }));
+            }
+
+            in_card = true;
+            described = false;
+            list = None;
+            continue;
+        }
+
+        if !in_card {
+            read_field(column, &mut list, line).map_err(|e| at(n, e))?;
+            continue;
+        }
+
+        let card = match column.get_mut("cards") {
+            Some(Value::Array(cards)) => match cards.last_mut() {
+                Some(Value::Object(card)) => card,
+                _ => return Err(at(n, String::from("expected a card")))
+            },
+            _ => return Err(at(n, String::from("expected a card")))
+        };
+
+        let line = line.strip_prefix("  ")
+                    .ok_or_else(|| at(n, String::from("card details are indented by two spaces")))?;
+
+        if let Some(item) = line.strip_prefix("- [ ]").or(line.strip_prefix("- [x]")) {
+            if let Some(Value::Array(checklist)) = card.get_mut("checklist") {
+                checklist.push(json!({
+                    "step_name": parse_text(item.strip_prefix(' ').unwrap_or(item)),
+                    "step_duration": 0,
+                    "done": line.starts_with("- [x]")
+                }));
+            }
+        } else if let Some(text) = line.strip_prefix('>') {
+            let text = text.strip_prefix(' ').unwrap_or(text);
+
+            if let Some(Value::String(description)) = card.get_mut("description") {
+                if described {
+                    description.push('\n');
+                }
+                description.push_str(text);
+                described = true;
+            }
+        } else {
+            read_field(card, &mut list, line).map_err(|e| at(n, e))?;
+        }
+    }
+
+    project.insert(String::from("columns"), Value::Array(columns.into_iter().map(Value::Object).collect()));
+
+    Ok((Value::Object(project), schema_version))
+}
+
+fn parse_daily_tasks(text: &str) -> Result<(Vec<Value>, u32), String> {
+    let mut lines = text.lines().enumerate().map(|(n, line)| (n + 1, line));
+
+    let (_, schema_version) = read_front_matter(&mut lines)?;
+    let mut tasks: Vec<Map<String, Value>> = Vec::new();
+    let mut list: Option<String> = None;
+
+    for (n, line) in lines {
+
+        if line.trim().is_empty() || line.starts_with("# ") {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("- ").or(if line == "-" {Some("")} else {None}) {
+            tasks.push(json_map(json!({ "task_name": parse_text(name) })));
+            list = None;
+            continue;
+        }
+
+        let task = tasks.last_mut().ok_or_else(|| at(n, String::from("expected a task")))?;
+        let line = line.strip_prefix("  ")
+                    .ok_or_else(|| at(n, String::from("task details are indented by two spaces")))?;
+
+        read_field(task, &mut list, line).map_err(|e| at(n, e))?;
+    }
+
+    Ok((tasks.into_iter().map(Value::Object).collect(), schema_version))
+}
+
+fn parse_labels(text: &str) -> Result<(Map<String, Value>, u32), String> {
+    let mut lines = text.lines().enumerate().map(|(n, line)| (n + 1, line));
+
+    let (_, schema_version) = read_front_matter(&mut lines)?;
+    let mut labels = Map::new();
+    let mut list: Option<String> = None;
+
+    for (n, line) in lines {
+
+        if line.trim().is_empty() || line.starts_with("# ") {
+            continue;
+        }
+
+        read_field(&mut labels, &mut list, line).map_err(|e| at(n, e))?;
+    }
+
+    Ok((labels.into_iter().map(|(label, color)| (parse_text(&label), color)).collect(), schema_version))
+}
+
+// Fields between the --- lines at the top of a file, without the schema version
+fn read_front_matter<'a, I: Iterator<Item = (usize, &'a str)>>(lines: &mut I) -> Result<(Map<String, Value>, u32), String> {
+    let mut fields = Map::new();
+    let mut list: Option<String> = None;
+
+    lines.next();
+
+    loop {
+        match lines.next() {
+            Some((_, "---")) => break,
+            Some((n, line)) => read_field(&mut fields, &mut list, line).map_err(|e| at(n, e))?,
+            None => return Err(String::from("the front matter is not closed with ---"))
+        }
+    }
+
+    let schema_version = match fields.remove("schema_version") {
+        Some(Value::Number(version)) => version.as_u64().map(|v| v as u32),
+        _ => None
+    }.ok_or_else(|| String::from("schema_version is missing from the front matter"))?;
+
+    Ok((fields, schema_version))
+}
+
+fn read_field(map: &mut Map<String, Value>, list: &mut Option<String>, line: &str) -> Result<(), String> {
+
+    if let Some(item) = line.strip_prefix("  - ") {
+        let key = list.as_ref().ok_or_else(|| String::from("list item without a key"))?;
+
+        if let Some(Value::Array(items)) = map.get_mut(key) {
+            items.push(parse_scalar(item));
+        }
+    } else if let Some((key, value)) = line.split_once(':') {
+
+        match value.strip_prefix(' ').unwrap_or(value) {
+            "" => {
+                map.insert(String::from(key), Value::Array(Vec::new()));
+                *list = Some(String::from(key));
+            },
+            value => {
+                map.insert(String::from(key), parse_scalar(value));
+                *list = None;
+            }
+        }
+    } else {
+        return Err(format!("expected `key: value`, found `{}`", line));
+    }
+
+    Ok(())
+}
+
+fn json_map(value: Value) -> Map<String, Value> {
+
+    match value {
+        Value::Object(map) => map,
+        _ => Map::new()
+    }
+}
+
+fn at(line: usize, error: String) -> String {
+
+    format!("line {}: {}", line, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{Local, NaiveDate};
+
+    use super::*;
+    use crate::data::{Card, Priority, TaskStep, Transition};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kanban-markdown-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn cards_read_back_as_they_were_written() {
+        let dir = temp_dir("round-trip");
+
+        // Ctrl+Enter puts line breaks into titles and names
+        let mut card = Card::new("Fix the header\non mobile");
+        card.number = 1;
+        card.labels = vec![String::from("bug"), String::from("needs review")];
+        card.priority = Priority::High;
+        card.due = NaiveDate::from_ymd_opt(2021, 12, 24);
+        card.description = String::from("First line\n\n  indented\nnumber: 7");
+        card.checklist = vec![TaskStep::new("Reproduce", Duration::from_secs(0)), TaskStep::new("- [x] fix", Duration::from_secs(0))];
+        card.checklist[0].done = true;
+        card.transitions = vec![Transition { from: String::from("ToDo"), to: String::from("In Progress"), at: Local::now() }];
+
+        let mut project = KanbanProject::new(String::from("Website\nand shop"));
+        project.next_card_number = 2;
+        project.columns[0].name = String::from("\"Someday\"");
+        project.columns[1].name = String::from("In\nProgress");
+        project.columns[1].cards.push(card);
+
+        let mut labels = Labels::new();
+        labels.insert(String::from("bug"), tui::style::Color::Red);
+        labels.insert(String::from("needs\nreview"), tui::style::Color::Blue);
+
+        let mut storage = MarkdownStorage::new(&dir);
+        storage.save(std::slice::from_ref(&project), &[Task::new("Stand\nup")], &labels).unwrap();
+
+        let board = storage.load().ok().unwrap();
+
+        assert_eq!(serde_json::to_value(&board.projects).unwrap(), serde_json::to_value([&project]).unwrap());
+        assert_eq!(board.daily_tasks[0].task_name, "Stand\nup");
+        assert_eq!(board.labels, labels);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_the_board_did_not_write_are_left_alone() {
+        let dir = temp_dir("foreign");
+        let notes = "---\ntitle: Notes\n---\n\n# Notes\n";
+        fs::write(dir.join("notes.md"), notes).unwrap();
+
+        let mut storage = MarkdownStorage::new(&dir);
+        storage.save(&[KanbanProject::new(String::from("Website"))], &[], &Labels::new()).unwrap();
+
+        let board = storage.load().ok().unwrap();
+        assert_eq!(board.projects.len(), 1);
+
+        storage.save(&[], &[], &Labels::new()).unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("notes.md")).unwrap(), notes);
+        assert!(!dir.join("website.md").exists());
+
+        storage.save(&[KanbanProject::new(String::from("Shop"))], &[Task::new("Standup")], &Labels::new()).unwrap();
+        let broken = temp_dir("foreign-broken");
+        move_board_files(&dir, &broken).unwrap();
+
+        assert!(broken.join("shop.md").is_file() && broken.join(DAILY_TASKS_FILE).is_file());
+        assert_eq!(fs::read_to_string(dir.join("notes.md")).unwrap(), notes);
+        assert!(storage.load().ok().unwrap().projects.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&broken).unwrap();
+    }
+}
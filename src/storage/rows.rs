@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+
+    hash::Hash
+};
+
+use serde_json::{Map, Value};
+
+use crate::data::{KanbanProject, Labels, Task};
+
+use super::LoadError;
+
+// * Rows
+// The board split into one JSON object per project, column, card and daily
+// task with its place on the board, and the label palette. Saving compares the rows with what was
+// last written, so only the parts that changed are written. A column row
+// holds the ids of its cards in order and cards hold no place of their own,
+// moving a card rewrites the one or two columns it left and went to
+#[derive(Default)]
+pub struct Rows {
+    pub projects: HashMap<String, ProjectRow>,
+    pub columns: HashMap<(String, i64), String>,
+    pub cards: HashMap<String, String>,
+    pub daily_tasks: HashMap<i64, String>,
+    // None in boards saved before it had a palette
+    pub labels: Option<String>
+}
+
+#[derive(PartialEq)]
+pub struct ProjectRow {
+    pub position: i64,
+    pub data: String
+}
+
+impl Rows {
+
+    pub fn from_board(projects: &[KanbanProject], daily_tasks: &[Task], labels: &Labels) -> serde_json::Result<Rows> {
+        let mut rows = Rows::default();
+
+        for (project_position, project) in projects.iter().enumerate() {
+            let mut project_data = serde_json::to_value(project)?;
+            remove_key(&mut project_data, "columns");
+
+            rows.projects.insert(project.id.clone(), ProjectRow {
+                position: project_position as i64,
+                data: project_data.to_string()
+            });
+
+            for (column_position, column) in project.columns.iter().enumerate() {
+                let mut column_data = serde_json::to_value(column)?;
+                let ids: Vec<&String> = column.cards.iter().map(|card| &card.id).collect();
+
+                if let Value::Object(map) = &mut column_data {
+                    map.insert(String::from("cards"), serde_json::to_value(ids)?);
+                }
+
+                rows.columns.insert((project.id.clone(), column_position as i64), column_data.to_string());
+
+                for card in &column.cards {
+                    rows.cards.insert(card.id.clone(), serde_json::to_string(card)?);
+                }
+            }
+        }
+
+        for (position, task) in daily_tasks.iter().enumerate() {
+            rows.daily_tasks.insert(position as i64, serde_json::to_string(task)?);
+        }
+
+        rows.labels = Some(serde_json::to_string(labels)?);
+
+        Ok(rows)
+    }
+
+    // Puts the rows back together into the same document the JSON file holds
+    pub fn to_document(&self, schema_version: u32) -> Result<Value, LoadError> {
+        let mut projects: Vec<(&String, &ProjectRow)> = self.projects.iter().collect();
+        projects.sort_by_key(|(_, row)| row.position);
+
+        let mut projects: Vec<(&String, Map<String, Value>)> = projects.into_iter()
+            .map(|(id, row)| {
+                let mut project = parse_object(&row.data, &format!("project {}", id))?;
+                project.insert(String::from("columns"), Value::Array(Vec::new()));
+
+                Ok((id, project))
+            })
+            .collect::<Result<_, LoadError>>()?;
+
+        let mut columns: HashMap<(&String, i64), Map<String, Value>> = HashMap::new();
+
+        for ((project_id, position), data) in &self.columns {
+            let what = format!("column {} of project {}", position, project_id);
+            let mut column = parse_object(data, &what)?;
+
+            let ids = match column.remove("cards") {
+                Some(Value::Array(ids)) => ids,
+                _ => return Err(LoadError::Schema(format!("{} has no list of cards", what)))
+            };
+
+            let cards = ids.iter()
+                .map(|id| {
+                    let card = id.as_str().and_then(|id| self.cards.get(id))
+                                .ok_or_else(|| LoadError::Schema(format!("{} holds card {}, which does not exist", what, id)))?;
+
+                    serde_json::from_str(card).map_err(|e| LoadError::Schema(format!("card {}: {}", id, e)))
+                })
+                .collect::<Result<Vec<Value>, LoadError>>()?;
+
+            column.insert(String::from("cards"), Value::Array(cards));
+            columns.insert((project_id, *position), column);
+        }
+
+        let mut columns: Vec<_> = columns.into_iter().collect();
+        columns.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for ((project_id, _), column) in columns {
+            if let Some((_, project)) = projects.iter_mut().find(|(id, _)| *id == project_id) {
+                if let Some(Value::Array(project_columns)) = project.get_mut("columns") {
+                    project_columns.push(Value::Object(column));
+                }
+            }
+        }
+
+        let mut daily_tasks: Vec<(&i64, &String)> = self.daily_tasks.iter().collect();
+        daily_tasks.sort_by_key(|(position, _)| **position);
+
+        let daily_tasks = daily_tasks.into_iter()
+            .map(|(position, data)| serde_json::from_str(data)
+                                    .map_err(|e| LoadError::Schema(format!("daily task {}: {}", position, e))))
+            .collect::<Result<Vec<Value>, LoadError>>()?;
+
+        let mut document = serde_json::json!({
+            "schema_version": schema_version,
+            "projects": projects.into_iter().map(|(_, project)| Value::Object(project)).collect::<Vec<Value>>(),
+            "daily_tasks": daily_tasks
+        });
+
+        if let Some(labels) = &self.labels {
+            document["labels"] = serde_json::from_str(labels).map_err(|e| LoadError::Schema(format!("labels: {}", e)))?;
+        }
+
+        Ok(document)
+    }
+}
+
+fn remove_key(value: &mut Value, key: &str) {
+
+    if let Value::Object(map) = value {
+        map.remove(key);
+    }
+}
+
+fn parse_object(data: &str, what: &str) -> Result<Map<String, Value>, LoadError> {
+
+    match serde_json::from_str(data) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(LoadError::Schema(format!("{} is not an object", what))),
+        Err(e) => Err(LoadError::Schema(format!("{}: {}", what, e)))
+    }
+}
+
+// Rows in `new` that are not in `old` or differ, and keys only `old` has
+pub fn changes<'a, K: Eq + Hash, V: PartialEq>(old: &'a HashMap<K, V>, new: &'a HashMap<K, V>) -> (Vec<(&'a K, &'a V)>, Vec<&'a K>) {
+    let changed = new.iter().filter(|(key, row)| old.get(key) != Some(row)).collect();
+    let removed = old.keys().filter(|key| !new.contains_key(key)).collect();
+
+    (changed, removed)
+}
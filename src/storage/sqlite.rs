@@ -0,0 +1,305 @@
+use std::{
+    fs, io,
+
+    path::{Path, PathBuf}
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+
+use crate::{
+    data::{KanbanProject, Labels, Task},
+    schema::SCHEMA_VERSION
+};
+
+use super::{
+    decode, empty_board, LoadError, LoadedBoard, Stamp, Storage,
+    rows::{changes, ProjectRow, Rows}
+};
+
+const TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS projects (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS columns (
+        project_id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (project_id, position)
+    );
+    CREATE TABLE IF NOT EXISTS cards (
+        id TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS daily_tasks (
+        position INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+";
+
+// * SQLite backend
+// Every project, column and card is its own row holding its JSON, a column
+// holds the ids of its cards in order. Saving compares the board with what was
+// last written and only touches the rows that changed, so moving a card
+// rewrites one or two columns instead of every card behind it
+pub struct SqliteStorage {
+    path: PathBuf,
+    connection: Option<Connection>,
+    // rows as they are in the database
+    written: Rows,
+    schema_version: Option<u32>,
+    // the database file right after the last load or save, `written` is
+    // stale when another program changed it since
+    stamp: Stamp
+}
+
+impl SqliteStorage {
+
+    pub fn new(path: &Path) -> SqliteStorage {
+
+        SqliteStorage {
+            path: path.to_path_buf(),
+            connection: None,
+            written: Rows::default(),
+            schema_version: None,
+            stamp: Stamp::default()
+        }
+    }
+}
+
+// Opened on first use so a missing database is only created by a save
+fn connect<'a>(connection: &'a mut Option<Connection>, path: &Path) -> rusqlite::Result<&'a mut Connection> {
+
+    if connection.is_none() {
+        let opened = Connection::open(path)?;
+        opened.execute_batch(TABLES)?;
+
+        *connection = Some(opened);
+    }
+
+    Ok(connection.as_mut().unwrap())
+}
+
+impl Storage for SqliteStorage {
+
+    fn load(&mut self) -> Result<LoadedBoard, LoadError> {
+
+        if !self.path.exists() {
+            return Ok(empty_board());
+        }
+
+        let (document, rows, schema_version) = read_document(connect(&mut self.connection, &self.path).map_err(LoadError::Database)?)?;
+
+        self.written = rows;
+        self.schema_version = schema_version;
+        self.stamp = self.stamp();
+
+        match document {
+            Some(document) => decode(document, None),
+            None => Ok(empty_board())
+        }
+    }
+
+    fn save(&mut self, projects: &[KanbanProject], daily_tasks: &[Task], labels: &Labels) -> io::Result<()> {
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let rows = Rows::from_board(projects, daily_tasks, labels)?;
+
+        let stale = self.stamp() != self.stamp;
+        let connection = connect(&mut self.connection, &self.path).map_err(io::Error::other)?;
+
+        // overwriting changes of another program, diff against what is really there
+        if stale {
+            let (_, written, schema_version) = read_document(connection)
+                                                .map_err(|e| io::Error::other(e.message()))?;
+
+            self.written = written;
+            self.schema_version = schema_version;
+        }
+
+        let write_version = self.schema_version != Some(SCHEMA_VERSION);
+        write_changes(connection, &self.written, &rows, write_version).map_err(io::Error::other)?;
+
+        self.written = rows;
+        self.schema_version = Some(SCHEMA_VERSION);
+        self.stamp = self.stamp();
+
+        Ok(())
+    }
+
+    fn stamp(&self) -> Stamp {
+
+        Stamp::of(vec![self.path.clone()])
+    }
+}
+
+fn write_changes(connection: &mut Connection, old: &Rows, new: &Rows, write_version: bool) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+
+    if write_version {
+        transaction.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?1)",
+            params![SCHEMA_VERSION.to_string()])?;
+    }
+
+    if old.labels != new.labels {
+        if let Some(labels) = &new.labels {
+            transaction.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('labels', ?1)", params![labels])?;
+        }
+    }
+
+    let (changed, removed) = changes(&old.projects, &new.projects);
+
+    for id in removed {
+        transaction.execute("DELETE FROM projects WHERE id = ?1", params![id])?;
+    }
+    for (id, row) in changed {
+        transaction.execute("INSERT OR REPLACE INTO projects (id, position, data) VALUES (?1, ?2, ?3)",
+            params![id, row.position, row.data])?;
+    }
+
+    let (changed, removed) = changes(&old.columns, &new.columns);
+
+    for (project_id, position) in removed {
+        transaction.execute("DELETE FROM columns WHERE project_id = ?1 AND position = ?2", params![project_id, position])?;
+    }
+    for ((project_id, position), data) in changed {
+        transaction.execute("INSERT OR REPLACE INTO columns (project_id, position, data) VALUES (?1, ?2, ?3)",
+            params![project_id, position, data])?;
+    }
+
+    let (changed, removed) = changes(&old.cards, &new.cards);
+
+    for id in removed {
+        transaction.execute("DELETE FROM cards WHERE id = ?1", params![id])?;
+    }
+    for (id, data) in changed {
+        transaction.execute("INSERT OR REPLACE INTO cards (id, data) VALUES (?1, ?2)", params![id, data])?;
+    }
+
+    let (changed, removed) = changes(&old.daily_tasks, &new.daily_tasks);
+
+    for position in removed {
+        transaction.execute("DELETE FROM daily_tasks WHERE position = ?1", params![position])?;
+    }
+    for (position, data) in changed {
+        transaction.execute("INSERT OR REPLACE INTO daily_tasks (position, data) VALUES (?1, ?2)", params![position, data])?;
+    }
+
+    transaction.commit()
+}
+
+// The same document the JSON file holds, None for a database nothing was saved to yet
+fn read_document(connection: &Connection) -> Result<(Option<Value>, Rows, Option<u32>), LoadError> {
+    let mut rows = Rows::default();
+
+    let schema_version: Option<String> = connection
+        .query_row("SELECT value FROM meta WHERE key = 'schema_version'", [], |row| row.get(0))
+        .optional()
+        .map_err(LoadError::Database)?;
+
+    let schema_version = match schema_version {
+        Some(version) => version.parse::<u32>()
+                            .map_err(|_| LoadError::Schema(format!("schema_version is not a number: {}", version)))?,
+        None => return Ok((None, rows, None))
+    };
+
+    // the label palette is kept in meta
+    rows.labels = connection
+        .query_row("SELECT value FROM meta WHERE key = 'labels'", [], |row| row.get(0))
+        .optional()
+        .map_err(LoadError::Database)?;
+
+    let mut statement = connection.prepare("SELECT id, position, data FROM projects")
+                            .map_err(LoadError::Database)?;
+    let project_rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, ProjectRow { position: row.get(1)?, data: row.get(2)? })))
+                            .map_err(LoadError::Database)?;
+
+    for project in project_rows {
+        let (id, row) = project.map_err(LoadError::Database)?;
+        rows.projects.insert(id, row);
+    }
+
+    let mut statement = connection.prepare("SELECT project_id, position, data FROM columns")
+                            .map_err(LoadError::Database)?;
+    let column_rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?)))
+                            .map_err(LoadError::Database)?;
+
+    for column in column_rows {
+        let (project_id, position, data) = column.map_err(LoadError::Database)?;
+        rows.columns.insert((project_id, position), data);
+    }
+
+    let mut statement = connection.prepare("SELECT id, data FROM cards")
+                            .map_err(LoadError::Database)?;
+    let card_rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+                            .map_err(LoadError::Database)?;
+
+    for card in card_rows {
+        let (id, data) = card.map_err(LoadError::Database)?;
+        rows.cards.insert(id, data);
+    }
+
+    let mut statement = connection.prepare("SELECT position, data FROM daily_tasks")
+                            .map_err(LoadError::Database)?;
+    let task_rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+                            .map_err(LoadError::Database)?;
+
+    for task in task_rows {
+        let (position, data) = task.map_err(LoadError::Database)?;
+        rows.daily_tasks.insert(position, data);
+    }
+
+    let document = rows.to_document(schema_version)?;
+
+    Ok((Some(document), rows, Some(schema_version)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Card, Kanban};
+
+    #[test]
+    fn the_database_reads_back_every_saved_change() {
+        let dir = std::env::temp_dir().join(format!("kanban-sqlite-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("kanban.db");
+
+        let mut kanban = Kanban::default();
+        kanban.add_project(KanbanProject::new(String::from("Website")));
+        kanban.define_label("bug", Some(tui::style::Color::Red));
+
+        for title in ["Fix the header", "Write the docs", "Set up CI"] {
+            kanban.add_card(Card::new(title));
+        }
+
+        let mut storage = SqliteStorage::new(&path);
+        let daily_tasks = vec![Task::new("Standup")];
+        storage.save(&kanban.projects, &daily_tasks, &kanban.labels).unwrap();
+
+        // a move to the last column, then the one in the middle is deleted
+        kanban.move_card(2);
+        kanban.column_index = 1;
+        kanban.delete_column();
+        storage.save(&kanban.projects, &daily_tasks, &kanban.labels).unwrap();
+
+        let board = SqliteStorage::new(&path).load().ok().unwrap();
+
+        assert_eq!(board.projects[0].columns.len(), 2);
+        assert_eq!(board.projects[0].columns[1].cards[0].title, "Fix the header");
+        assert_eq!(serde_json::to_value(&board.projects).unwrap(), serde_json::to_value(&kanban.projects).unwrap());
+        assert_eq!(board.daily_tasks.len(), 1);
+        assert_eq!(board.labels, kanban.labels);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}